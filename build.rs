@@ -0,0 +1,20 @@
+#[cfg(feature = "header")]
+extern crate cbindgen;
+
+///With the `header` feature, regenerate `include/morbitgen.h` from `src/ffi.rs`
+fn main() {
+    #[cfg(feature = "header")]
+    header();
+}
+
+#[cfg(feature = "header")]
+fn header() {
+    use std::env;
+    println!("cargo:rerun-if-changed=src/ffi.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    let directory = env::var("CARGO_MANIFEST_DIR").unwrap();
+    let config = cbindgen::Config::from_file(format!("{}/cbindgen.toml", directory)).unwrap();
+    cbindgen::generate_with_config(&directory, config)
+        .expect("couldn't generate include/morbitgen.h")
+        .write_to_file(format!("{}/include/morbitgen.h", directory));
+}
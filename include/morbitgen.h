@@ -0,0 +1,84 @@
+#ifndef MORBITGEN_H
+#define MORBITGEN_H
+
+/* Generated by cbindgen from src/ffi.rs, don't edit */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+//What went wrong, anything but `Ok` has a message from `morbitgen_last_error`
+typedef enum {
+  MORBITGEN_STATUS_OK = 0,
+  //a required pointer was null
+  MORBITGEN_STATUS_NULL_ARGUMENT = 1,
+  //a string wasn't valid UTF-8, or had a nul in it going out
+  MORBITGEN_STATUS_INVALID_STRING = 2,
+  //a template, presets or generated character couldn't be read
+  MORBITGEN_STATUS_PARSE_ERROR = 3,
+  //no template with that name
+  MORBITGEN_STATUS_UNKNOWN_TEMPLATE = 4,
+  //the formatting couldn't be parsed or used
+  MORBITGEN_STATUS_FORMAT_ERROR = 5,
+  //a bug in morbitgen, the call did nothing
+  MORBITGEN_STATUS_PANIC = 6,
+  //the presets enable or disable a mutation that doesn't exist
+  MORBITGEN_STATUS_UNKNOWN_MUTATION = 7,
+  //a template inherits from one that isn't registered or from itself, or is still inherited from
+  MORBITGEN_STATUS_INHERITANCE_ERROR = 8,
+} MorbitgenStatus;
+
+//A resolved template, the mutations it can be generated with and its labels for formatting
+typedef struct MorbitgenTemplate MorbitgenTemplate;
+
+//Message for the last call on this thread that failed, null if none has. Valid until the next failing
+//call on the same thread, and not to be freed
+const char *morbitgen_last_error(void);
+
+//Free a string returned by morbitgen, null is ignored
+void morbitgen_free_string(char *string);
+
+//Register a template from a string under a name, replacing any template already called that.
+//`format` is `json`, `yaml`, `toml` or `ron` and json if null. It can inherit from any registered
+//template, and templates inheriting from one it replaces pick up the change
+MorbitgenStatus morbitgen_template_register(const char *name,
+                                            const char *source,
+                                            const char *format);
+
+//Unregister a template nothing else inherits from. Handles already taken from it still work
+MorbitgenStatus morbitgen_template_unregister(const char *name);
+
+//Names of the registered templates as a json list
+MorbitgenStatus morbitgen_template_names(char **out);
+
+//Get a registered template by name, later changes to the registry don't affect it
+MorbitgenStatus morbitgen_template_get(const char *name, MorbitgenTemplate **out);
+
+//Load a template from a string without registering it, `format` is as for
+//`morbitgen_template_register`. It can inherit from any registered template
+MorbitgenStatus morbitgen_template_load(const char *source,
+                                        const char *format,
+                                        MorbitgenTemplate **out);
+
+//Free a template, null is ignored
+void morbitgen_template_free(MorbitgenTemplate *template_);
+
+//Generate a character as a json object, `presets` is a json list like `["color:red"]` and can be null.
+//The names of the mutations applied are written to `mutations` as a json list unless it is null. The
+//same seed and presets give the same character on any platform with the same version of morbitgen
+MorbitgenStatus morbitgen_generate(const MorbitgenTemplate *template_,
+                                   const char *presets,
+                                   uint64_t seed,
+                                   char **out,
+                                   char **mutations);
+
+//Format a character from `morbitgen_generate` with a formatting entry or string, or `json`, `markdown`
+//and `html`
+MorbitgenStatus morbitgen_format(const MorbitgenTemplate *template_,
+                                 const char *generated,
+                                 const char *formatting,
+                                 char **out);
+
+#endif /* MORBITGEN_H */
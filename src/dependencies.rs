@@ -0,0 +1,279 @@
+use super::{Attribute, Generator, Template};
+use super::mutation::MUTATION;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DependencyKind {
+    ///a `requires` on the attribute or one of its options
+    Requires,
+    ///uses the other attribute's options, which doesn't need it to be generated first
+    Reuse,
+    ///copies the other attribute's value
+    Copy,
+    ///a parent's attribute renamed
+    Rename,
+}
+
+///`to` depends on `from`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dependency {
+    pub from: String,
+    pub to: String,
+    pub kind: DependencyKind,
+    ///the requirement, with the option path in front if an option has it
+    pub label: String,
+}
+
+impl Dependency {
+    ///Whether `from` has to be generated before `to` for this to work
+    pub fn ordered(&self) -> bool {
+        self.kind == DependencyKind::Requires || self.kind == DependencyKind::Copy
+    }
+}
+
+impl Template {
+    ///Every dependency between attributes, in the order attributes are generated
+    pub fn dependencies(&self) -> Vec<Dependency> {
+        let mut dependencies = Vec::new();
+        let mut names = self.columns();
+        names.extend(self.attributes.keys().filter(|name| !names.contains(name)).cloned().collect::<Vec<_>>());
+        for name in &names {
+            if let Some(attribute) = self.attributes.get(name) {
+                add_dependencies(name, "", attribute, &mut dependencies);
+            }
+        }
+        for (old, new) in &self.rename {
+            dependencies.push(Dependency {
+                from: old.clone(),
+                to: new.clone(),
+                kind: DependencyKind::Rename,
+                label: "rename".to_string(),
+            });
+        }
+        dependencies
+    }
+
+    ///Groups of attributes that depend on each other in a loop, not counting renames
+    pub fn cycles(&self) -> Vec<Vec<String>> {
+        let dependencies: Vec<Dependency> =
+            self.dependencies().into_iter().filter(|dependency| dependency.kind != DependencyKind::Rename).collect();
+        let mut names: Vec<&String> = Vec::new();
+        for dependency in &dependencies {
+            for name in &[&dependency.from, &dependency.to] {
+                if !names.contains(name) {
+                    names.push(name);
+                }
+            }
+        }
+        //Tarjan's strongly connected components
+        struct State {
+            index: Vec<Option<usize>>,
+            low: Vec<usize>,
+            stack: Vec<usize>,
+            on_stack: Vec<bool>,
+            next: usize,
+            components: Vec<Vec<usize>>,
+        }
+        fn visit(v: usize, edges: &[Vec<usize>], state: &mut State) {
+            state.index[v] = Some(state.next);
+            state.low[v] = state.next;
+            state.next += 1;
+            state.stack.push(v);
+            state.on_stack[v] = true;
+            for &w in &edges[v] {
+                match state.index[w] {
+                    None => {
+                        visit(w, edges, state);
+                        state.low[v] = state.low[v].min(state.low[w]);
+                    }
+                    Some(index) if state.on_stack[w] => state.low[v] = state.low[v].min(index),
+                    Some(_) => {}
+                }
+            }
+            if Some(state.low[v]) == state.index[v] {
+                let mut component = Vec::new();
+                while let Some(w) = state.stack.pop() {
+                    state.on_stack[w] = false;
+                    component.push(w);
+                    if w == v {
+                        break;
+                    }
+                }
+                state.components.push(component);
+            }
+        }
+        let position = |name: &String| names.iter().position(|other| *other == name).unwrap();
+        let mut edges = vec![Vec::new(); names.len()];
+        for dependency in &dependencies {
+            edges[position(&dependency.from)].push(position(&dependency.to));
+        }
+        let mut state = State {
+            index: vec![None; names.len()],
+            low: vec![0; names.len()],
+            stack: Vec::new(),
+            on_stack: vec![false; names.len()],
+            next: 0,
+            components: Vec::new(),
+        };
+        for v in 0..names.len() {
+            if state.index[v].is_none() {
+                visit(v, &edges, &mut state);
+            }
+        }
+        state
+            .components
+            .into_iter()
+            .filter(|component| component.len() > 1 || edges[component[0]].contains(&component[0]))
+            .map(|mut component| {
+                component.sort();
+                component.into_iter().map(|i| names[i].clone()).collect()
+            })
+            .rev()
+            .collect()
+    }
+
+    ///Attributes in an order where everything is generated after what it depends on, otherwise keeping
+    ///the declared order. Attributes in a cycle go where they were declared
+    ///
+    ///Reusing an attribute doesn't need it generated first, but its options' requirements apply to the
+    ///attribute reusing it
+    pub fn dependency_order(&self) -> Vec<String> {
+        let columns = self.columns();
+        let mut edges: Vec<(String, String)> = Vec::new();
+        let dependencies = self.dependencies();
+        for dependency in dependencies.iter().filter(|dependency| dependency.ordered()) {
+            edges.push((dependency.from.clone(), dependency.to.clone()));
+        }
+        //follow reuses until nothing new is added, for reuses of reuses
+        loop {
+            let mut added = false;
+            for reuse in dependencies.iter().filter(|dependency| dependency.kind == DependencyKind::Reuse) {
+                let inherited: Vec<(String, String)> = edges
+                    .iter()
+                    .filter(|(_, to)| *to == reuse.from)
+                    .map(|(from, _)| (from.clone(), reuse.to.clone()))
+                    .filter(|edge| !edges.contains(edge))
+                    .collect();
+                added |= !inherited.is_empty();
+                edges.extend(inherited);
+            }
+            if !added {
+                break;
+            }
+        }
+        edges.retain(|(from, to)| from != to && columns.contains(from) && columns.contains(to));
+
+        let cycles = self.cycles();
+        let same_cycle = |a: &String, b: &String| cycles.iter().any(|cycle| cycle.contains(a) && cycle.contains(b));
+        let mut order = Vec::new();
+        let mut remaining = columns.clone();
+        while !remaining.is_empty() {
+            //the first declared attribute with nothing left to wait for, besides the rest of its cycle
+            let next = remaining
+                .iter()
+                .position(|name| {
+                    !edges
+                        .iter()
+                        .any(|(from, to)| to == name && remaining.contains(from) && !same_cycle(from, to))
+                })
+                .unwrap_or(0);
+            order.push(remaining.remove(next));
+        }
+        order
+    }
+
+    ///Generate in `dependency_order` from now on
+    pub fn sort_order(&mut self) {
+        self.order = self.dependency_order();
+    }
+
+    ///Dependencies where what's needed is generated after what needs it, so it never sees a value
+    pub fn order_violations(&self) -> Vec<Dependency> {
+        let columns = self.columns();
+        let position = |name: &String| columns.iter().position(|column| column == name);
+        self.dependencies()
+            .into_iter()
+            .filter(|dependency| dependency.ordered())
+            .filter(|dependency| match (position(&dependency.from), position(&dependency.to)) {
+                (Some(from), Some(to)) => from >= to,
+                _ => false,
+            })
+            .collect()
+    }
+}
+
+fn add_dependencies(name: &str, path: &str, attribute: &Attribute, dependencies: &mut Vec<Dependency>) {
+    for requirement in &attribute.requires {
+        let label = if path.is_empty() {
+            requirement.to_string()
+        } else {
+            format!("{}: {}", path, requirement)
+        };
+        for (key, _, _) in &requirement.possibilities {
+            if key.is_empty() || key == MUTATION {
+                continue;
+            }
+            let dependency = Dependency {
+                from: key.clone(),
+                to: name.to_string(),
+                kind: DependencyKind::Requires,
+                label: label.clone(),
+            };
+            if !dependencies.contains(&dependency) {
+                dependencies.push(dependency);
+            }
+        }
+    }
+    let (target, kind, label) = match attribute.generator {
+        Generator::Choose(ref options) => {
+            for (option, value) in options {
+                let path = if path.is_empty() { option.clone() } else { format!("{} > {}", path, option) };
+                add_dependencies(name, &path, value, dependencies);
+            }
+            return;
+        }
+        Generator::Reuse(ref target) => (target, DependencyKind::Reuse, "reuse"),
+        Generator::Same(ref target) => (target, DependencyKind::Copy, "copy"),
+        Generator::Nothing => return,
+    };
+    let dependency = Dependency {
+        from: target.clone(),
+        to: name.to_string(),
+        kind,
+        label: if path.is_empty() { label.to_string() } else { format!("{}: {}", path, label) },
+    };
+    if !dependencies.contains(&dependency) {
+        dependencies.push(dependency);
+    }
+}
+
+#[test]
+fn test_dependency_order() {
+    let mut template: Template = r#"{"order": ["shade", "color", "size", "mood", "tone"], "attributes": {
+        "shade": {"copy": "color"},
+        "color": {"choose": {"red": {}, "blue": {"requires": ["size:large"]}}},
+        "size": {"choose": ["small", "large"]},
+        "mood": {"choose": {"happy": {"requires": ["tone:calm"]}}},
+        "tone": {"choose": {"calm": {"requires": ["mood:happy"]}}}
+    }}"#.parse().unwrap();
+    let violations: Vec<(String, String)> =
+        template.order_violations().into_iter().map(|dependency| (dependency.from, dependency.to)).collect();
+    assert_eq!(violations, vec![("color".to_string(), "shade".to_string()), ("size".to_string(), "color".to_string()), ("tone".to_string(), "mood".to_string())]);
+    assert_eq!(template.dependency_order(), vec!["size", "color", "shade", "mood", "tone"]);
+    template.sort_order();
+    let generated = template.generate(vec!["size:large".parse().unwrap()]);
+    assert_eq!(generated["shade"], generated["color"]);
+
+    let mut registry = super::TemplateRegistry::new();
+    registry.add("parent", r#"{"auto_order": true, "order": ["shade", "color"], "attributes": {
+        "shade": {"copy": "color"},
+        "color": {"choose": ["red", "blue"]}
+    }}"#).unwrap();
+    registry.add("child", r#"{"extends": "parent", "order": ["size"], "attributes": {
+        "color": {"choose": {"blue": {"requires": ["size:large"]}}},
+        "size": {"choose": ["small", "large"]}
+    }}"#).unwrap();
+    registry.resolve().unwrap();
+    let child = registry.get("child").unwrap();
+    assert_eq!(child.order, vec!["size", "color", "shade"]);
+    assert!(child.order_violations().is_empty());
+}
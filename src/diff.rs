@@ -0,0 +1,249 @@
+use super::{Attribute, Attributes, Chance, Generator, Requirement, Template};
+use std::collections::BTreeMap;
+use std::fmt::{self, Display, Formatter};
+
+///One difference between two resolved templates, options are paths like `wings > bug > beetle`
+#[derive(Debug, PartialEq)]
+pub enum Change {
+    AddedAttribute(String),
+    RemovedAttribute(String),
+    AddedOption(String),
+    RemovedOption(String),
+    Chance(String, Chance, Chance),
+    Requires(String, Vec<Requirement>, Vec<Requirement>),
+    ///what an attribute or option generates changed kind, like from choosing to reusing
+    Generator(String, String, String),
+    AddedToOrder(String),
+    RemovedFromOrder(String),
+    ///moved to after the attribute given, or to the start
+    Moved(String, Option<String>),
+    ///how likely a value is, assuming every requirement is met
+    Probability(String, f64, f64),
+}
+
+impl Display for Change {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let requirements = |requires: &Vec<Requirement>| {
+            requires.iter().map(|requirement| requirement.to_string()).collect::<Vec<_>>().join(", ")
+        };
+        match *self {
+            Change::AddedAttribute(ref name) => write!(f, "+ attribute {}", name),
+            Change::RemovedAttribute(ref name) => write!(f, "- attribute {}", name),
+            Change::AddedOption(ref path) => write!(f, "+ option {}", path),
+            Change::RemovedOption(ref path) => write!(f, "- option {}", path),
+            Change::Chance(ref path, old, new) => write!(f, "~ chance {}: {:?} -> {:?}", path, old, new),
+            Change::Requires(ref path, ref old, ref new) => {
+                write!(f, "~ requires {}: [{}] -> [{}]", path, requirements(old), requirements(new))
+            }
+            Change::Generator(ref path, ref old, ref new) => write!(f, "~ {}: {} -> {}", path, old, new),
+            Change::AddedToOrder(ref name) => write!(f, "+ order {}", name),
+            Change::RemovedFromOrder(ref name) => write!(f, "- order {}", name),
+            Change::Moved(ref name, Some(ref after)) => write!(f, "~ order {} moved after {}", name, after),
+            Change::Moved(ref name, None) => write!(f, "~ order {} moved to the start", name),
+            Change::Probability(ref path, old, new) => {
+                write!(f, "~ probability {}: {:.1}% -> {:.1}%", path, old * 100.0, new * 100.0)
+            }
+        }
+    }
+}
+
+///Differences going from `old` to `new`, with changes in how likely each value is if `probabilities` is set
+pub fn diff(old: &Template, new: &Template, probabilities: bool) -> Vec<Change> {
+    let mut changes = Vec::new();
+    diff_order(&old.columns(), &new.columns(), &mut changes);
+    for (name, attribute) in &old.attributes {
+        if !new.attributes.contains_key(name) {
+            changes.push(Change::RemovedAttribute(name.clone()));
+        } else {
+            diff_attribute(name, attribute, &new.attributes[name], &mut changes);
+        }
+    }
+    for name in new.attributes.keys() {
+        if !old.attributes.contains_key(name) {
+            changes.push(Change::AddedAttribute(name.clone()));
+        }
+    }
+    if probabilities {
+        for name in new.attributes.keys().filter(|name| old.attributes.contains_key(*name)) {
+            let old = old.probabilities(name);
+            let new = new.probabilities(name);
+            for value in old.keys().chain(new.keys().filter(|value| !old.contains_key(*value))) {
+                let before = old.get(value).cloned().unwrap_or(0.0);
+                let after = new.get(value).cloned().unwrap_or(0.0);
+                //anything too small to show isn't worth listing
+                if (before - after).abs() >= 0.0005 {
+                    changes.push(Change::Probability(format!("{} > {}", name, value), before, after));
+                }
+            }
+        }
+    }
+    changes
+}
+
+fn diff_order(old: &[String], new: &[String], changes: &mut Vec<Change>) {
+    for name in old.iter().filter(|name| !new.contains(name)) {
+        changes.push(Change::RemovedFromOrder(name.clone()));
+    }
+    for name in new.iter().filter(|name| !old.contains(name)) {
+        changes.push(Change::AddedToOrder(name.clone()));
+    }
+    //whatever isn't in the longest common subsequence moved
+    let old: Vec<&String> = old.iter().filter(|name| new.contains(name)).collect();
+    let new: Vec<&String> = new.iter().filter(|name| old.contains(name)).collect();
+    let mut lengths = vec![vec![0; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i][j] = if old[i] == new[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+    let mut kept = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            kept.push(old[i]);
+            i += 1;
+            j += 1;
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    for (j, name) in new.iter().enumerate() {
+        if !kept.contains(name) {
+            changes.push(Change::Moved(name.to_string(), if j > 0 { Some(new[j - 1].clone()) } else { None }));
+        }
+    }
+}
+
+fn describe(generator: &Generator) -> String {
+    match *generator {
+        Generator::Choose(_) => "choose".to_string(),
+        Generator::Reuse(ref name) => format!("reuse {}", name),
+        Generator::Same(ref name) => format!("copy {}", name),
+        Generator::Nothing => "nothing".to_string(),
+    }
+}
+
+fn diff_attribute(path: &str, old: &Attribute, new: &Attribute, changes: &mut Vec<Change>) {
+    let (old_chance, new_chance) = (old.chance.unwrap_or(Chance::Standard), new.chance.unwrap_or(Chance::Standard));
+    if old_chance != new_chance {
+        changes.push(Change::Chance(path.to_string(), old_chance, new_chance));
+    }
+    if old.requires != new.requires {
+        changes.push(Change::Requires(path.to_string(), old.requires.clone(), new.requires.clone()));
+    }
+    match (&old.generator, &new.generator) {
+        (Generator::Choose(old), Generator::Choose(new)) => diff_options(path, old, new, changes),
+        (old, new) => {
+            let (old, new) = (describe(old), describe(new));
+            if old != new {
+                changes.push(Change::Generator(path.to_string(), old, new));
+            }
+        }
+    }
+}
+
+fn diff_options(path: &str, old: &Attributes, new: &Attributes, changes: &mut Vec<Change>) {
+    for (name, option) in old {
+        let path = format!("{} > {}", path, name);
+        match new.get(name) {
+            Some(new) => diff_attribute(&path, option, new, changes),
+            None => changes.push(Change::RemovedOption(path)),
+        }
+    }
+    for name in new.keys().filter(|name| !old.contains_key(*name)) {
+        changes.push(Change::AddedOption(format!("{} > {}", path, name)));
+    }
+}
+
+impl Template {
+    ///How likely each value of an attribute is, assuming every requirement is met
+    pub fn probabilities(&self, name: &str) -> BTreeMap<String, f64> {
+        let mut probabilities = BTreeMap::new();
+        if let Some(attribute) = self.attributes.get(name) {
+            attribute.generator.probabilities(1.0, &mut probabilities, &self.attributes, &mut vec![name]);
+        }
+        probabilities
+    }
+}
+
+impl Generator {
+    fn probabilities<'a>(
+        &'a self,
+        probability: f64,
+        probabilities: &mut BTreeMap<String, f64>,
+        attributes: &'a Attributes,
+        visited: &mut Vec<&'a str>,
+    ) {
+        match *self {
+            Generator::Choose(ref options) => {
+                //the same as generating, a chance is picked by weight then an option with that chance
+                let mut choices: BTreeMap<Chance, Vec<&String>> = BTreeMap::new();
+                for (option, value) in options {
+                    match value.chance.unwrap_or(Chance::Standard) {
+                        Chance::Always => {
+                            choices.clear();
+                            choices.insert(Chance::Standard, vec![option]);
+                            break;
+                        }
+                        Chance::Never => {}
+                        chance => choices.entry(chance).or_default().push(option),
+                    }
+                }
+                let total: u32 = choices.keys().map(|chance| chance.chance()).sum();
+                for (chance, choices) in &choices {
+                    let each = probability * f64::from(chance.chance()) / f64::from(total) / choices.len() as f64;
+                    for option in choices {
+                        match options[*option].generator {
+                            Generator::Nothing => *probabilities.entry(option.to_string()).or_insert(0.0) += each,
+                            ref generator => generator.probabilities(each, probabilities, attributes, visited),
+                        }
+                    }
+                }
+            }
+            Generator::Reuse(ref name) | Generator::Same(ref name) => {
+                if visited.contains(&name.as_str()) {
+                    return;
+                }
+                visited.push(name);
+                if let Some(attribute) = attributes.get(name) {
+                    attribute.generator.probabilities(probability, probabilities, attributes, visited);
+                }
+            }
+            Generator::Nothing => {}
+        }
+    }
+}
+
+#[test]
+fn test_diff() {
+    let old: Template = r#"{"order": ["size", "color", "mood"], "attributes": {
+        "size": {"choose": ["small", "large"]},
+        "color": {"choose": {"red": {}, "blue": {"requires": ["size:large"]}}},
+        "mood": {"choose": ["happy"]}
+    }}"#.parse().unwrap();
+    let new: Template = r#"{"order": ["color", "size", "shade"], "attributes": {
+        "size": {"choose": {"small": {}, "large": "Rare", "huge": "Rare"}},
+        "color": {"choose": {"red": {}, "blue": {"requires": ["size:huge"]}}},
+        "shade": {"reuse": "color"}
+    }}"#.parse().unwrap();
+    let changes: Vec<String> = diff(&old, &new, true).iter().map(|change| change.to_string()).collect();
+    assert_eq!(changes, vec![
+        "- order mood",
+        "+ order shade",
+        "~ order size moved after color",
+        "~ requires color > blue: [size:large] -> [size:huge]",
+        "- attribute mood",
+        "~ chance size > large: Standard -> Rare",
+        "+ option size > huge",
+        "+ attribute shade",
+        "~ probability size > large: 50.0% -> 11.5%",
+        "~ probability size > small: 50.0% -> 76.9%",
+        "~ probability size > huge: 0.0% -> 11.5%",
+    ]);
+}
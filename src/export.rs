@@ -0,0 +1,75 @@
+use super::{Generated, Requirement, Template};
+use std::io::{self, Write};
+
+impl Template {
+    pub fn generate_batch(&self, presets: &[Requirement], count: usize) -> Vec<Generated> {
+        (0..count).map(|_| self.generate(presets.to_vec())).collect()
+    }
+
+    ///One column per attribute in order after renaming, the same for every batch from this template
+    pub fn columns(&self) -> Vec<String> {
+        let mut columns: Vec<String> = Vec::new();
+        for name in &self.order {
+            let name = self.rename.get(name).unwrap_or(name);
+            if !columns.contains(name) {
+                columns.push(name.clone());
+            }
+        }
+        columns
+    }
+
+    ///Write a header and one row per character separated by `delimiter`, attributes that weren't
+    ///generated are left empty
+    pub fn write_delimited<W: Write>(&self, characters: &[Generated], delimiter: char, writer: &mut W) -> io::Result<()> {
+        write_delimited(&self.columns(), characters, delimiter, writer)
+    }
+}
+
+///Write a header of `columns` and one row per character separated by `delimiter`, for characters from
+///more than one template or with mutations adding attributes
+pub fn write_delimited<W: Write>(columns: &[String], characters: &[Generated], delimiter: char, writer: &mut W) -> io::Result<()> {
+    write_row(writer, columns.iter().map(|column| column.as_str()), delimiter)?;
+    for generated in characters {
+        let row = columns.iter().map(|column| generated.get(column).map_or("", |value| value.as_str()));
+        write_row(writer, row, delimiter)?;
+    }
+    Ok(())
+}
+
+fn write_row<'a, W: Write, I: Iterator<Item = &'a str>>(writer: &mut W, row: I, delimiter: char) -> io::Result<()> {
+    let fields: Vec<String> = row.map(|field| quote(field, delimiter)).collect();
+    writeln!(writer, "{}", fields.join(&delimiter.to_string()))
+}
+
+///Quote a field if it has the delimiter, a quote or a line break in it
+fn quote(field: &str, delimiter: char) -> String {
+    if field.contains([delimiter, '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[test]
+fn test_delimited() {
+    let template = Template::new_from_string(r#"{"order": ["hands", "color", "hair style"], "rename": {"hands": "wings"}, "attributes": {
+        "wings": {"choose": {"big, \"fluffy\"": {}}},
+        "color": {"choose": {"chartreuse\nyellow green": {}}},
+        "hair style": {"choose": {"curly": {}}, "requires": ["hair length:long"]}
+    }}"#, None);
+    let characters = template.generate_batch(&[], 2);
+    let mut csv = Vec::new();
+    template.write_delimited(&characters, ',', &mut csv).unwrap();
+    let row = "\"big, \"\"fluffy\"\"\",\"chartreuse\nyellow green\",\n";
+    assert_eq!(String::from_utf8(csv).unwrap(), format!("wings,color,hair style\n{}{}", row, row));
+    let mut tsv = Vec::new();
+    template.write_delimited(&characters[..1], '\t', &mut tsv).unwrap();
+    let row = "\"big, \"\"fluffy\"\"\"\t\"chartreuse\nyellow green\"\t\n";
+    assert_eq!(String::from_utf8(tsv).unwrap(), format!("wings\tcolor\thair style\n{}", row));
+    let columns = vec!["wings".to_string(), "size".to_string()];
+    let mut extra = characters[0].clone();
+    extra.insert("size".to_string(), "huge".to_string());
+    let mut csv = Vec::new();
+    write_delimited(&columns, &[characters[0].clone(), extra], ',', &mut csv).unwrap();
+    assert_eq!(String::from_utf8(csv).unwrap(), "wings,size\n\"big, \"\"fluffy\"\"\",\n\"big, \"\"fluffy\"\"\",huge\n");
+}
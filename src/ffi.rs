@@ -1,31 +1,341 @@
-use super::{Template, Requirement};
+//!C interface, see `include/morbitgen.h` which `cargo build --features header` regenerates
+//!
+//!Strings going in are borrowed and have to be UTF-8, strings coming out belong to the caller and have
+//!to be freed with `morbitgen_free_string`. Every function returning a status leaves a message for
+//!`morbitgen_last_error` when it isn't `Ok`. Pointers have to be null or valid for what they point to,
+//!and handles and strings can't be used after they are freed
+
+//safety is the same for every function, as above
+#![allow(clippy::missing_safety_doc)]
+
+use super::{Format, Generated, Locale, Mutations, Requirement, Template, seeded, templates};
+use std::cell::RefCell;
+use std::ffi::{CStr, CString};
 use std::os::raw::c_char;
-use std::ffi::{CString, CStr};
-use std::collections::HashMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
 use serde_json;
 
+///What went wrong, anything but `Ok` has a message from `morbitgen_last_error`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MorbitgenStatus {
+    Ok = 0,
+    ///a required pointer was null
+    NullArgument = 1,
+    ///a string wasn't valid UTF-8, or had a nul in it going out
+    InvalidString = 2,
+    ///a template, presets or generated character couldn't be read
+    ParseError = 3,
+    ///no template with that name
+    UnknownTemplate = 4,
+    ///the formatting couldn't be parsed or used
+    FormatError = 5,
+    ///a bug in morbitgen, the call did nothing
+    Panic = 6,
+    ///the presets enable or disable a mutation that doesn't exist
+    UnknownMutation = 7,
+    ///a template inherits from one that isn't registered or from itself, or is still inherited from
+    InheritanceError = 8,
+}
+
+///A resolved template, the mutations it can be generated with and its labels for formatting
+pub struct MorbitgenTemplate {
+    template: Template,
+    mutations: Mutations,
+    labels: Locale,
+}
+
+impl MorbitgenTemplate {
+    fn new(template: Template, mutations: &Mutations) -> MorbitgenTemplate {
+        let labels = template.labels();
+        MorbitgenTemplate { template, mutations: mutations.clone(), labels }
+    }
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+struct Error(MorbitgenStatus, String);
+
+fn set_last_error(message: String) {
+    let message = CString::new(message.replace('\0', "")).unwrap();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+///Run `f`, turning errors and panics into a status and last error
+fn status<F: FnOnce() -> Result<(), Error>>(f: F) -> MorbitgenStatus {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => MorbitgenStatus::Ok,
+        Ok(Err(Error(status, message))) => {
+            set_last_error(message);
+            status
+        }
+        Err(_) => {
+            set_last_error("morbitgen panicked".to_string());
+            MorbitgenStatus::Panic
+        }
+    }
+}
+
+unsafe fn string<'a>(pointer: *const c_char, name: &str) -> Result<&'a str, Error> {
+    if pointer.is_null() {
+        return Err(Error(MorbitgenStatus::NullArgument, format!("{} is null", name)));
+    }
+    CStr::from_ptr(pointer)
+        .to_str()
+        .map_err(|e| Error(MorbitgenStatus::InvalidString, format!("{}: {}", name, e)))
+}
+
+unsafe fn reference<'a, T>(pointer: *const T, name: &str) -> Result<&'a T, Error> {
+    pointer.as_ref().ok_or_else(|| Error(MorbitgenStatus::NullArgument, format!("{} is null", name)))
+}
+
+unsafe fn output<T>(out: *mut T, value: T) -> Result<(), Error> {
+    if out.is_null() {
+        return Err(Error(MorbitgenStatus::NullArgument, "out is null".to_string()));
+    }
+    *out = value;
+    Ok(())
+}
+
+unsafe fn format(format: *const c_char) -> Result<Format, Error> {
+    if format.is_null() {
+        return Ok(Format::Json);
+    }
+    let format = string(format, "format")?;
+    Format::from_extension(format).ok_or_else(|| Error(MorbitgenStatus::ParseError, format!("unknown format {}", format)))
+}
+
+fn c_string(string: String) -> Result<CString, Error> {
+    CString::new(string).map_err(|e| Error(MorbitgenStatus::InvalidString, e.to_string()))
+}
+
+fn owned_string(string: String) -> Result<*mut c_char, Error> {
+    c_string(string).map(CString::into_raw)
+}
+
+///Message for the last call on this thread that failed, null if none has. Valid until the next failing
+///call on the same thread, and not to be freed
 #[no_mangle]
-pub fn generate(template: *mut c_char, presets: *mut c_char) -> *mut c_char {
-    let template = unsafe { CStr::from_ptr(template).to_string_lossy().to_string() };
-    let presets = unsafe { CStr::from_ptr(presets).to_string_lossy().to_owned() };
-    let generated = if let Some(template) = TEMPLATES.get(&template) {
-        let presets: Vec<Requirement> = serde_json::from_str(&presets).unwrap();
-        serde_json::to_string(&template.generate(presets)).unwrap()
-    } else {
-        "{species:unknown}".to_string()
-    };
-    CString::new(generated.as_str()).unwrap().into_raw()
+pub extern "C" fn morbitgen_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |message| message.as_ptr()))
 }
 
+///Free a string returned by morbitgen, null is ignored
+#[no_mangle]
+pub unsafe extern "C" fn morbitgen_free_string(string: *mut c_char) {
+    if !string.is_null() {
+        drop(CString::from_raw(string));
+    }
+}
 
+///Register a template from a string under a name, replacing any template already called that.
+///`format` is `json`, `yaml`, `toml` or `ron` and json if null. It can inherit from any registered
+///template, and templates inheriting from one it replaces pick up the change
+#[no_mangle]
+pub unsafe extern "C" fn morbitgen_template_register(
+    name: *const c_char,
+    source: *const c_char,
+    format: *const c_char,
+) -> MorbitgenStatus {
+    status(|| {
+        let name = string(name, "name")?;
+        let source = string(source, "source")?;
+        let template: Template = self::format(format)?.parse(source).map_err(|e| Error(MorbitgenStatus::ParseError, e))?;
+        templates().register(name, template).map_err(|e| Error(MorbitgenStatus::InheritanceError, e))
+    })
+}
+
+///Unregister a template nothing else inherits from. Handles already taken from it still work
+#[no_mangle]
+pub unsafe extern "C" fn morbitgen_template_unregister(name: *const c_char) -> MorbitgenStatus {
+    status(|| {
+        let name = string(name, "name")?;
+        let mut templates = templates();
+        if templates.source(name).is_none() {
+            return Err(Error(MorbitgenStatus::UnknownTemplate, format!("unknown template {}", name)));
+        }
+        templates.remove(name).map(|_| ()).map_err(|e| Error(MorbitgenStatus::InheritanceError, e))
+    })
+}
+
+///Names of the registered templates as a json list
+#[no_mangle]
+pub unsafe extern "C" fn morbitgen_template_names(out: *mut *mut c_char) -> MorbitgenStatus {
+    status(|| {
+        let names = serde_json::to_string(&templates().names()).unwrap();
+        output(out, owned_string(names)?)
+    })
+}
+
+///Get a registered template by name, later changes to the registry don't affect it
+#[no_mangle]
+pub unsafe extern "C" fn morbitgen_template_get(name: *const c_char, out: *mut *mut MorbitgenTemplate) -> MorbitgenStatus {
+    status(|| {
+        let name = string(name, "name")?;
+        let templates = templates();
+        let template = templates
+            .get(name)
+            .ok_or_else(|| Error(MorbitgenStatus::UnknownTemplate, format!("unknown template {}", name)))?;
+        let handle = MorbitgenTemplate::new(template.clone(), templates.mutations());
+        output(out, Box::into_raw(Box::new(handle)))
+    })
+}
+
+///Load a template from a string without registering it, `format` is as for
+///`morbitgen_template_register`. It can inherit from any registered template
+#[no_mangle]
+pub unsafe extern "C" fn morbitgen_template_load(
+    source: *const c_char,
+    format: *const c_char,
+    out: *mut *mut MorbitgenTemplate,
+) -> MorbitgenStatus {
+    status(|| {
+        let source = string(source, "source")?;
+        let template: Template = self::format(format)?.parse(source).map_err(|e| Error(MorbitgenStatus::ParseError, e))?;
+        let templates = templates();
+        let template = templates
+            .resolve_with("loaded", &template)
+            .map_err(|e| Error(MorbitgenStatus::InheritanceError, e))?;
+        let handle = MorbitgenTemplate::new(template, templates.mutations());
+        output(out, Box::into_raw(Box::new(handle)))
+    })
+}
 
+///Free a template, null is ignored
+#[no_mangle]
+pub unsafe extern "C" fn morbitgen_template_free(template: *mut MorbitgenTemplate) {
+    if !template.is_null() {
+        drop(Box::from_raw(template));
+    }
+}
+
+///Generate a character as a json object, `presets` is a json list like `["color:red"]` and can be null.
+///The names of the mutations applied are written to `mutations` as a json list unless it is null. The
+///same seed and presets give the same character on any platform with the same version of morbitgen
+#[no_mangle]
+pub unsafe extern "C" fn morbitgen_generate(
+    template: *const MorbitgenTemplate,
+    presets: *const c_char,
+    seed: u64,
+    out: *mut *mut c_char,
+    mutations: *mut *mut c_char,
+) -> MorbitgenStatus {
+    status(|| {
+        let handle = reference(template, "template")?;
+        let presets: Vec<Requirement> = if presets.is_null() {
+            Vec::new()
+        } else {
+            serde_json::from_str(string(presets, "presets")?)
+                .map_err(|e| Error(MorbitgenStatus::ParseError, format!("presets: {}", e)))?
+        };
+        let mut random = seeded(seed);
+        let (generated, applied) = handle
+            .template
+            .generate_mutated_with_rng(presets, &handle.mutations, &mut random)
+            .map_err(|e| Error(MorbitgenStatus::UnknownMutation, e))?;
+        let json = handle.template.format_with(&generated, "json", &handle.labels).map_err(|e| Error(MorbitgenStatus::FormatError, e))?;
+        if out.is_null() {
+            return Err(Error(MorbitgenStatus::NullArgument, "out is null".to_string()));
+        }
+        //both are converted before either is handed over, so a failure doesn't leave one to free
+        let json = c_string(json)?;
+        let applied = c_string(serde_json::to_string(&applied).unwrap())?;
+        if !mutations.is_null() {
+            *mutations = applied.into_raw();
+        }
+        output(out, json.into_raw())
+    })
+}
+
+///Format a character from `morbitgen_generate` with a formatting entry or string, or `json`, `markdown`
+///and `html`
+#[no_mangle]
+pub unsafe extern "C" fn morbitgen_format(
+    template: *const MorbitgenTemplate,
+    generated: *const c_char,
+    formatting: *const c_char,
+    out: *mut *mut c_char,
+) -> MorbitgenStatus {
+    status(|| {
+        let handle = reference(template, "template")?;
+        let generated: Generated = serde_json::from_str(string(generated, "generated")?)
+            .map_err(|e| Error(MorbitgenStatus::ParseError, format!("generated: {}", e)))?;
+        let formatting = string(formatting, "formatting")?;
+        let formatted = handle.template.format_with(&generated, formatting, &handle.labels).map_err(|e| Error(MorbitgenStatus::FormatError, e))?;
+        output(out, owned_string(formatted)?)
+    })
+}
+
+#[cfg(test)]
 lazy_static! {
-    static ref TEMPLATES: HashMap<String, Template> = {
-        let mut m = HashMap::new();
-        let base_template = Template::new_from_string(include_str!("../assets/base.json"), None);
-        let obj_template = Template::new_from_string(include_str!("../assets/obj.json"), Some(&base_template));
-        m.insert("base".to_string(), base_template);
-        m.insert("obj".to_string(), obj_template);
-        m
-    };
+    ///Held by tests using the shared registry, so one registering templates doesn't change what another sees
+    static ref REGISTRY_TEST: ::std::sync::Mutex<()> = ::std::sync::Mutex::new(());
+}
+
+#[test]
+fn test_ffi() {
+    let _lock = REGISTRY_TEST.lock().unwrap_or_else(|e| e.into_inner());
+    unsafe {
+        let mut template = ptr::null_mut();
+        let name = CString::new("obj").unwrap();
+        assert_eq!(morbitgen_template_get(name.as_ptr(), &mut template), MorbitgenStatus::Ok);
+        let presets = CString::new(r#"["flavor:normal"]"#).unwrap();
+        let mut first = ptr::null_mut();
+        let mut second = ptr::null_mut();
+        assert_eq!(morbitgen_generate(template, presets.as_ptr(), 7, &mut first, ptr::null_mut()), MorbitgenStatus::Ok);
+        assert_eq!(morbitgen_generate(template, presets.as_ptr(), 7, &mut second, ptr::null_mut()), MorbitgenStatus::Ok);
+        assert_eq!(CStr::from_ptr(first), CStr::from_ptr(second));
+        let formatting = CString::new("[species]").unwrap();
+        let mut formatted = ptr::null_mut();
+        assert_eq!(morbitgen_format(template, first, formatting.as_ptr(), &mut formatted), MorbitgenStatus::Ok);
+        assert_eq!(CStr::from_ptr(formatted).to_str().unwrap(), "obj");
+        morbitgen_free_string(first);
+        morbitgen_free_string(second);
+        morbitgen_free_string(formatted);
+        morbitgen_template_free(template);
+
+        let source = CString::new("extends: nothing").unwrap();
+        let format = CString::new("yaml").unwrap();
+        assert_eq!(morbitgen_template_load(source.as_ptr(), format.as_ptr(), &mut template), MorbitgenStatus::InheritanceError);
+        assert_eq!(CStr::from_ptr(morbitgen_last_error()).to_str().unwrap(), "loaded inherits from unknown template nothing");
+        assert_eq!(morbitgen_generate(ptr::null(), ptr::null(), 0, &mut first, ptr::null_mut()), MorbitgenStatus::NullArgument);
+    }
+}
+
+#[test]
+fn test_ffi_register() {
+    let _lock = REGISTRY_TEST.lock().unwrap_or_else(|e| e.into_inner());
+    unsafe {
+        let mut template = ptr::null_mut();
+        let mut first = ptr::null_mut();
+        let format = CString::new("yaml").unwrap();
+        let parent = CString::new("test_ffi_register parent").unwrap();
+        let child = CString::new("test_ffi_register child").unwrap();
+        let source = CString::new(r#"{"order": ["size"], "attributes": {"size": {"choose": ["tiny"]}}}"#).unwrap();
+        assert_eq!(morbitgen_template_register(parent.as_ptr(), source.as_ptr(), ptr::null()), MorbitgenStatus::Ok);
+        let source =
+            CString::new("extends: test_ffi_register parent\norder: [mood]\nattributes:\n  mood:\n    choose: [calm]").unwrap();
+        assert_eq!(morbitgen_template_register(child.as_ptr(), source.as_ptr(), format.as_ptr()), MorbitgenStatus::Ok);
+        assert_eq!(morbitgen_template_get(child.as_ptr(), &mut template), MorbitgenStatus::Ok);
+        assert_eq!(morbitgen_template_unregister(parent.as_ptr()), MorbitgenStatus::InheritanceError);
+        assert_eq!(morbitgen_template_unregister(child.as_ptr()), MorbitgenStatus::Ok);
+        assert_eq!(morbitgen_template_unregister(child.as_ptr()), MorbitgenStatus::UnknownTemplate);
+        let mut mutations = ptr::null_mut();
+        assert_eq!(morbitgen_generate(template, ptr::null(), 0, &mut first, &mut mutations), MorbitgenStatus::Ok);
+        assert_eq!(CStr::from_ptr(first).to_str().unwrap(), "{\n  \"mood\": \"calm\",\n  \"size\": \"tiny\"\n}");
+        assert_eq!(CStr::from_ptr(mutations).to_str().unwrap(), "[]");
+        morbitgen_free_string(first);
+        morbitgen_free_string(mutations);
+        let presets = CString::new(r#"["mutation:nothing"]"#).unwrap();
+        assert_eq!(morbitgen_generate(template, presets.as_ptr(), 0, &mut first, ptr::null_mut()), MorbitgenStatus::UnknownMutation);
+        morbitgen_template_free(template);
+        assert_eq!(morbitgen_template_unregister(parent.as_ptr()), MorbitgenStatus::Ok);
+        let mut names = ptr::null_mut();
+        assert_eq!(morbitgen_template_names(&mut names), MorbitgenStatus::Ok);
+        assert_eq!(CStr::from_ptr(names).to_str().unwrap(), r#"["base","obj"]"#);
+        morbitgen_free_string(names);
+    }
 }
@@ -0,0 +1,97 @@
+use super::Template;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::path::Path;
+use {ron, serde_json, serde_yaml, toml};
+
+///File formats templates, mutations and locales can be written in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Yaml,
+    Toml,
+    Ron,
+}
+
+impl Format {
+    pub const ALL: &'static [Format] = &[Format::Json, Format::Yaml, Format::Toml, Format::Ron];
+
+    pub fn from_extension(extension: &str) -> Option<Format> {
+        match extension {
+            "json" => Some(Format::Json),
+            "yaml" | "yml" => Some(Format::Yaml),
+            "toml" => Some(Format::Toml),
+            "ron" => Some(Format::Ron),
+            _ => None,
+        }
+    }
+
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<Format> {
+        path.as_ref()
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .and_then(Format::from_extension)
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match *self {
+            Format::Json => "json",
+            Format::Yaml => "yaml",
+            Format::Toml => "toml",
+            Format::Ron => "ron",
+        }
+    }
+
+    pub fn parse<T: DeserializeOwned>(&self, string: &str) -> Result<T, String> {
+        match *self {
+            Format::Json => serde_json::from_str(string).map_err(|e| e.to_string()),
+            Format::Yaml => serde_yaml::from_str(string).map_err(|e| e.to_string()),
+            Format::Toml => toml::from_str(string).map_err(|e| e.to_string()),
+            Format::Ron => ron::de::from_str(string).map_err(|e| e.to_string()),
+        }
+    }
+
+    pub fn serialize<T: Serialize>(&self, value: &T) -> Result<String, String> {
+        match *self {
+            Format::Json => serde_json::to_string_pretty(value).map_err(|e| e.to_string()),
+            Format::Yaml => serde_yaml::to_string(value).map_err(|e| e.to_string()),
+            Format::Toml => toml::to_string_pretty(value).map_err(|e| e.to_string()),
+            Format::Ron => ron::ser::to_string_pretty(value, Default::default()).map_err(|e| e.to_string()),
+        }
+    }
+}
+
+impl Template {
+    ///Load a template from a file in whichever format its extension says
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Template, String> {
+        use std::fs;
+        let path = path.as_ref();
+        let format = Format::from_path(path).ok_or_else(|| format!("{}: unknown template format", path.display()))?;
+        let contents = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        format.parse(&contents).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+}
+
+///Convert a template from one format to another, attributes are written in the order they are generated
+pub fn convert(string: &str, from: Format, to: Format) -> Result<String, String> {
+    let template: Template = from.parse(string)?;
+    to.serialize(&template)
+}
+
+#[test]
+fn test_convert() {
+    let base = include_str!("../assets/base.json");
+    let obj = include_str!("../assets/obj.json");
+    let copy = r#"{"order": ["color", "shade"], "attributes": {"color": {"choose": {"red": {}}}, "shade": {"copy": "color"}}}"#;
+    for source in &[base, obj, copy] {
+        let json = convert(source, Format::Json, Format::Json).unwrap();
+        for &format in Format::ALL {
+            let converted = convert(source, Format::Json, format).unwrap();
+            assert_eq!(convert(&converted, format, Format::Json).unwrap(), json, "{:?}", format);
+        }
+    }
+    assert!(convert(copy, Format::Json, Format::Yaml).unwrap().contains("copy: color"));
+    let yaml = convert(base, Format::Json, Format::Yaml).unwrap();
+    let species = yaml.find("species:").unwrap();
+    assert!(species < yaml.find("head size:").unwrap() && yaml.find("head size:").unwrap() < yaml.find("has:").unwrap());
+}
@@ -0,0 +1,463 @@
+use super::{Generated, Locale, Requirement, meets_requirement};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::sync::RwLock;
+
+///Turns a value into something else, gets the argument from `[variable|filter:argument]` if there is one
+pub type Filter = fn(&str, Option<&str>) -> String;
+
+const BUILT_IN_FILTERS: &[&str] = &["upper", "lower", "capitalize", "title", "trim", "plural", "a", "default", "id", "description"];
+
+lazy_static! {
+    ///Filters registered on top of the built in ones, used by every formatting
+    static ref FILTERS: RwLock<BTreeMap<String, Filter>> = Default::default();
+}
+
+///Add a filter that can be used as `[variable|name]` in any formatting parsed after this, replacing any built
+///in filter with the same name
+pub fn register_filter(name: &str, filter: Filter) {
+    FILTERS.write().unwrap_or_else(|e| e.into_inner()).insert(name.to_string(), filter);
+}
+
+fn registered_filter(name: &str) -> Option<Filter> {
+    FILTERS.read().unwrap_or_else(|e| e.into_inner()).get(name).cloned()
+}
+
+///Text with `[variable|filter]`s, `[condition?then|else]`s, `[switch attribute|value:text|*:text]`s and `[+verb]`s
+///that agree with the subject. `[a]` and `[A]` become whichever of `a` and `an` fits the word after them, and
+///`[variable|description]` is the description of the variable's value
+#[derive(Clone, Debug, Default)]
+pub struct Formatting {
+    contents: Vec<SubFormatting>,
+}
+
+#[derive(Clone, Debug)]
+enum SubFormatting {
+    Text(String),
+    ///attribute name and the filters to run on its value in order
+    Variable(String, Vec<(String, Option<String>)>),
+    ///all requirements have to be met for the first formatting, otherwise the second is used
+    Conditional(Vec<Requirement>, Formatting, Formatting),
+    ///formatting for the first value that matches the attribute, `*` matches anything
+    Switch(String, Vec<(String, Formatting)>),
+    ///verb conjugated to agree with the subject
+    Verb(String),
+    ///`a` or `an` for the word after it, capitalized if true
+    Article(bool),
+}
+
+///Stand-ins for `[a]` and `[A]` until the word after them is known
+const ARTICLE: char = '\u{E000}';
+const CAPITAL_ARTICLE: char = '\u{E001}';
+
+impl Formatting {
+    pub fn format(&self, generated: &Generated) -> String {
+        self.format_with(generated, None)
+    }
+
+    ///Format with values shown the way the locale displays them
+    pub fn format_with(&self, generated: &Generated, locale: Option<&Locale>) -> String {
+        let english = locale.is_none_or(|locale| locale.english);
+        let mut output = articles(&self.render(generated, locale), english);
+        for splitter in vec!["\n", " "].into_iter() {
+            output = output
+                .split(splitter)
+                .filter(|split| !split.is_empty())
+                .map(|split| split.trim())
+                .collect::<Vec<_>>()
+                .join(splitter);
+        }
+        output
+    }
+
+    fn render(&self, generated: &Generated, locale: Option<&Locale>) -> String {
+        let mut output = String::new();
+        let mut after_empty = false;
+        for formatting in &self.contents {
+            let mut string = match *formatting {
+                SubFormatting::Text(ref text) => text.clone(),
+                SubFormatting::Variable(ref variable, ref variable_filters) => {
+                    let attribute = variable.to_lowercase();
+                    //`id` shows the value itself instead of its label
+                    let id = variable_filters.iter().any(|(name, _)| name == "id");
+                    let mut value = generated.get(&attribute).map_or("".to_string(), |value| match locale {
+                        Some(locale) if !id => locale.value(&attribute, value).to_string(),
+                        _ => value.clone(),
+                    });
+                    for (name, argument) in variable_filters {
+                        let argument = argument.as_ref().map(|argument| argument.as_str());
+                        value = match registered_filter(name) {
+                            Some(filter) => filter(&value, argument),
+                            //needs the locale, so it can't be an ordinary filter
+                            None if name == "description" => generated
+                                .get(&attribute)
+                                .and_then(|value| locale?.description(&attribute, Some(value)))
+                                .unwrap_or("")
+                                .to_string(),
+                            None => filter(name, &value, argument),
+                        };
+                    }
+                    //the case of the name is a shorthand for upper and capitalize
+                    if &variable.to_uppercase() == variable {
+                        value.to_uppercase()
+                    } else if variable.chars().next().is_some_and(|c| c.is_uppercase()) {
+                        capitalize(&value)
+                    } else {
+                        value
+                    }
+                }
+                SubFormatting::Conditional(ref requirements, ref then, ref otherwise) => {
+                    let empty = Default::default();
+                    if requirements.iter().all(|requirement| {
+                        meets_requirement(requirement, generated, &empty, &Default::default())
+                    })
+                    {
+                        then.render(generated, locale)
+                    } else {
+                        otherwise.render(generated, locale)
+                    }
+                }
+                SubFormatting::Switch(ref attribute, ref cases) => {
+                    let value = generated.get(attribute);
+                    cases
+                        .iter()
+                        .find(|(case, _)| case == "*" || Some(case) == value)
+                        .map_or(String::new(), |(_, formatting)| formatting.render(generated, locale))
+                }
+                SubFormatting::Verb(ref verb) => conjugate(verb, generated),
+                SubFormatting::Article(capital) => (if capital { CAPITAL_ARTICLE } else { ARTICLE }).to_string(),
+            };
+            if let SubFormatting::Text(_) = *formatting {
+                //punctuation after something that came out empty goes where it would have been
+                if after_empty && string.trim_start().starts_with(|c| ".,;:!?".contains(c)) {
+                    string = string.trim_start().to_string();
+                }
+                after_empty = false;
+            } else if string.is_empty() {
+                //the space in front of it was for it
+                output.truncate(output.trim_end().len());
+                after_empty = true;
+            }
+            output += &string;
+        }
+        output
+    }
+}
+
+///Built in filters, the ones that don't apply to the value leave it as it is
+fn filter(name: &str, value: &str, argument: Option<&str>) -> String {
+    match name {
+        "upper" => value.to_uppercase(),
+        "lower" => value.to_lowercase(),
+        "capitalize" => capitalize(value),
+        "title" => value.split(' ').map(capitalize).collect::<Vec<_>>().join(" "),
+        "trim" => value.trim().to_string(),
+        "plural" => plural(value),
+        "a" if !value.is_empty() => format!("{} {}", article(value), value),
+        "default" if value.is_empty() => argument.unwrap_or("").to_string(),
+        _ => value.to_string(),
+    }
+}
+
+fn capitalize(value: &str) -> String {
+    let mut chars = value.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().collect::<String>() + chars.as_str(),
+        None => String::new(),
+    }
+}
+
+///English plural of the last word
+pub fn plural(value: &str) -> String {
+    const IRREGULAR: &[(&str, &str)] = &[
+        ("foot", "feet"),
+        ("tooth", "teeth"),
+        ("mouse", "mice"),
+        ("goose", "geese"),
+        ("child", "children"),
+        ("person", "people"),
+        ("fish", "fish"),
+        ("sheep", "sheep"),
+        ("deer", "deer"),
+    ];
+    let split = value.rfind(' ').map_or(0, |i| i + 1);
+    let (start, word) = value.split_at(split);
+    if word.is_empty() {
+        return value.to_string();
+    }
+    if let Some(&(_, plural)) = IRREGULAR.iter().find(|&&(singular, _)| singular == word) {
+        return format!("{}{}", start, plural);
+    }
+    let lowercase = word.to_lowercase();
+    if ["s", "x", "z", "ch", "sh"].iter().any(|end| lowercase.ends_with(end)) {
+        format!("{}es", value)
+    } else if lowercase.ends_with('y') && !lowercase[..lowercase.len() - 1].ends_with(|c| "aeiou".contains(c)) {
+        format!("{}ies", &value[..value.len() - 1])
+    } else {
+        format!("{}s", value)
+    }
+}
+
+///`a` or `an` depending on how the word starts
+pub fn article(word: &str) -> &'static str {
+    const CONSONANT_SOUNDS: &[&str] = &["uni", "use", "usu", "uti", "eu", "one", "once"];
+    const VOWEL_SOUNDS: &[&str] = &["hour", "honest", "honor", "heir"];
+    let word = word.to_lowercase();
+    if VOWEL_SOUNDS.iter().any(|start| word.starts_with(start)) {
+        "an"
+    } else if CONSONANT_SOUNDS.iter().any(|start| word.starts_with(start)) {
+        "a"
+    } else if word.starts_with(|c| "aeiou".contains(c)) {
+        "an"
+    } else {
+        "a"
+    }
+}
+
+///Replace the stand-ins for `[a]` with the article for the word after them, or drop them if not English
+fn articles(output: &str, english: bool) -> String {
+    let mut fixed = String::new();
+    for (i, c) in output.char_indices() {
+        if c != ARTICLE && c != CAPITAL_ARTICLE {
+            fixed.push(c);
+        } else if english {
+            let article = article(output[i + c.len_utf8()..].trim_start());
+            fixed += &if c == CAPITAL_ARTICLE { capitalize(article) } else { article.to_string() };
+        }
+    }
+    fixed
+}
+
+///Whether the subject takes verbs like he, she and it do, which is what the `is` attribute or the
+///subjective pronoun is used for
+fn third_person_singular(generated: &Generated) -> bool {
+    match generated.get("is") {
+        Some(is) => is == "is",
+        None => matches!(generated.get("subjective").map(|subjective| subjective.as_str()), Some("he" | "she" | "it")),
+    }
+}
+
+///Conjugate a verb in the present tense to agree with the subject
+fn conjugate(verb: &str, generated: &Generated) -> String {
+    let singular = third_person_singular(generated);
+    match verb {
+        "be" if singular => "is".to_string(),
+        "be" if generated.get("subjective").is_some_and(|subjective| subjective == "I") => "am".to_string(),
+        "be" => "are".to_string(),
+        "have" if singular => "has".to_string(),
+        _ if !singular => verb.to_string(),
+        _ if verb.ends_with('o') => format!("{}es", verb),
+        //third person verbs end the same way plural nouns do
+        _ => plural(verb),
+    }
+}
+
+///Find the first of `targets` that isn't escaped or inside brackets
+fn find_top_level(s: &str, targets: &[char]) -> Option<(usize, char)> {
+    let mut depth = 0;
+    let mut escape = false;
+    for (i, c) in s.char_indices() {
+        if escape {
+            escape = false;
+            continue;
+        }
+        match c {
+            '\\' => escape = true,
+            '[' => depth += 1,
+            ']' => depth -= 1,
+            c if depth == 0 && targets.contains(&c) => return Some((i, c)),
+            _ => (),
+        }
+    }
+    None
+}
+
+///Split on every `separator` that isn't escaped or inside brackets
+fn split_top_level(mut s: &str, separator: char) -> Vec<&str> {
+    let mut split = Vec::new();
+    while let Some((i, _)) = find_top_level(s, &[separator]) {
+        split.push(&s[..i]);
+        s = &s[i + separator.len_utf8()..];
+    }
+    split.push(s);
+    split
+}
+
+impl SubFormatting {
+    ///Parse what was between a pair of brackets
+    fn parse(s: &str) -> Result<SubFormatting, String> {
+        if s == "a" || s == "an" {
+            Ok(SubFormatting::Article(false))
+        } else if s == "A" || s == "An" {
+            Ok(SubFormatting::Article(true))
+        } else if let Some(verb) = s.strip_prefix('+') {
+            Ok(SubFormatting::Verb(verb.trim().to_string()))
+        } else if let Some(switch) = s.strip_prefix("switch ") {
+            let mut branches = split_top_level(switch, '|').into_iter();
+            let attribute = branches.next().unwrap_or("").trim().to_lowercase();
+            let mut cases = Vec::new();
+            for branch in branches {
+                match find_top_level(branch, &[':']) {
+                    Some((i, _)) => cases.push((branch[..i].trim().to_string(), branch[i + 1..].parse()?)),
+                    None => return Err(format!("switch case \"{}\" needs a value followed by :", branch)),
+                }
+            }
+            Ok(SubFormatting::Switch(attribute, cases))
+        } else if let Some((i, _)) = find_top_level(s, &['?']) {
+            let mut requirements = Vec::new();
+            for requirement in s[..i].split('&') {
+                requirements.push(requirement.parse()?);
+            }
+            let rest = &s[i + 1..];
+            let (then, otherwise) = match find_top_level(rest, &['|']) {
+                Some((i, _)) => (rest[..i].parse()?, rest[i + 1..].parse()?),
+                None => (rest.parse()?, Default::default()),
+            };
+            Ok(SubFormatting::Conditional(requirements, then, otherwise))
+        } else {
+            let mut split = split_top_level(s, '|').into_iter();
+            let variable = split.next().unwrap_or("").to_string();
+            let mut filters = Vec::new();
+            for filter in split {
+                let (name, argument) = match find_top_level(filter, &[':']) {
+                    Some((i, _)) => {
+                        let argument = filter[i + 1..].trim();
+                        let argument = if argument.len() >= 2 && argument.starts_with('"') && argument.ends_with('"') {
+                            &argument[1..argument.len() - 1]
+                        } else {
+                            argument
+                        };
+                        (filter[..i].trim(), Some(argument.to_string()))
+                    }
+                    None => (filter.trim(), None),
+                };
+                if !BUILT_IN_FILTERS.contains(&name) && registered_filter(name).is_none() {
+                    return Err(format!("unknown filter {} in \"[{}]\"", name, s));
+                }
+                filters.push((name.to_string(), argument));
+            }
+            Ok(SubFormatting::Variable(variable, filters))
+        }
+    }
+}
+
+impl FromStr for Formatting {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        //formatting used to start with a condition for all of it, which would now be printed as text
+        if let Some((i, _)) = find_top_level(s, &['?']) {
+            let condition = &s[..i];
+            if condition.contains(':') && !condition.contains('[') {
+                return Err(format!(
+                    "conditions go in brackets, write [{}?...] around the text for \"{}?\", or \\? for a question mark",
+                    condition, condition
+                ));
+            }
+        }
+        let mut contents = Vec::new();
+        let mut depth = 0;
+        let mut current = String::new();
+        let mut escape = false;
+        for c in s.chars() {
+            if escape {
+                escape = false;
+                //kept inside brackets so it can be handled when they get parsed
+                if depth > 0 {
+                    current.push('\\');
+                }
+                current.push(c);
+                continue;
+            }
+            match c {
+                '[' => {
+                    if depth == 0 {
+                        contents.push(SubFormatting::Text(current));
+                        current = String::new();
+                    } else {
+                        current.push(c);
+                    }
+                    depth += 1;
+                }
+                ']' => {
+                    if depth == 0 {
+                        return Err(format!("unmatched ] in \"{}\"", s));
+                    }
+                    depth -= 1;
+                    if depth == 0 {
+                        contents.push(SubFormatting::parse(&current)?);
+                        current = String::new();
+                    } else {
+                        current.push(c);
+                    }
+                }
+                '\\' => escape = true,
+                c => current.push(c),
+            }
+        }
+        if depth > 0 {
+            return Err(format!("unclosed [ in \"{}\"", s));
+        }
+        contents.push(SubFormatting::Text(current));
+        Ok(Formatting { contents })
+    }
+}
+
+#[test]
+fn test_formatting() {
+    let mut generated = Generated::new();
+    generated.insert("is".to_string(), "is".to_string());
+    generated.insert("morph".to_string(), "bat".to_string());
+    generated.insert("eye shape".to_string(), "round".to_string());
+    let format = |s: &str| s.parse::<Formatting>().unwrap().format(&generated);
+    assert_eq!(format("identif[is:is?ies|y]"), "identifies");
+    assert_eq!(format("identif[is:are?ies|y]"), "identify");
+    assert_eq!(format("[is:is&!eye shape:no?[eye shape] eyes|no eyes]"), "round eyes");
+    assert_eq!(format("[switch morph|bird:feathers|bat:leathery [morph] wings|*:skin]"), "leathery bat wings");
+    assert_eq!(format("[switch morph|bird:feathers|*:skin\\|scales]"), "skin|scales");
+    assert_eq!(format("what? [eye shape]"), "what? round");
+    assert_eq!(format("morph:bat\\? [eye shape]"), "morph:bat? round");
+    assert_eq!(
+        "morph:bat?[eye shape] eyes".parse::<Formatting>().unwrap_err(),
+        "conditions go in brackets, write [morph:bat?...] around the text for \"morph:bat?\", or \\? for a question mark"
+    );
+    assert!("[is:is?[eye shape]".parse::<Formatting>().is_err());
+    assert!("[is]]".parse::<Formatting>().is_err());
+}
+
+#[test]
+fn test_filters() {
+    let mut generated = Generated::new();
+    generated.insert("color".to_string(), "light brown".to_string());
+    generated.insert("morph".to_string(), "butterfly".to_string());
+    generated.insert("feet".to_string(), "large foot".to_string());
+    let format = |s: &str| s.parse::<Formatting>().unwrap().format(&generated);
+    assert_eq!(format("[color|upper] [color|title] [Color]"), "LIGHT BROWN Light Brown Light brown");
+    assert_eq!(format("[hair length|default:\"no\"] hair"), "no hair");
+    assert_eq!(format("[morph|plural], [feet|plural], [color|plural]"), "butterflies, large feet, light browns");
+
+    fn reverse(value: &str, _: Option<&str>) -> String {
+        value.chars().rev().collect()
+    }
+    assert_eq!("[color|reverse]".parse::<Formatting>().unwrap_err(), "unknown filter reverse in \"[color|reverse]\"");
+    register_filter("reverse", reverse);
+    assert_eq!(format("[color|reverse]"), "nworb thgil");
+    assert!("[color|uper]".parse::<Formatting>().is_err());
+}
+
+#[test]
+fn test_agreement() {
+    let mut generated = Generated::new();
+    generated.insert("subjective".to_string(), "she".to_string());
+    generated.insert("is".to_string(), "is".to_string());
+    generated.insert("height".to_string(), "average".to_string());
+    generated.insert("color".to_string(), "red".to_string());
+    let format = |generated: &Generated, s: &str| s.parse::<Formatting>().unwrap().format(generated);
+    assert_eq!(format(&generated, "[A] [height] obj, [an] [color] one. [height|a]"), "An average obj, a red one. an average");
+    assert_eq!(format(&generated, "a [height] obj, [a] [color:red?orange|blue] one"), "a average obj, an orange one");
+    assert_eq!(format(&generated, "[Subjective] [+identify] and [+go], [+be] [+have] [missing] ."), "She identifies and goes, is has.");
+    generated.insert("subjective".to_string(), "they".to_string());
+    generated.insert("is".to_string(), "are".to_string());
+    assert_eq!(format(&generated, "[Subjective] [+identify] and [+be]"), "They identify and are");
+    assert_eq!(format(&generated, "Couleur : [color] ! [missing], [height]"), "Couleur : red !, average");
+}
@@ -0,0 +1,163 @@
+use super::Template;
+use super::dependencies::{Dependency, DependencyKind};
+
+///How an edge is drawn, dependencies in a cycle are worse than ones generated in the wrong order
+#[derive(Clone, Copy, PartialEq)]
+enum Highlight {
+    Normal,
+    OrderViolation,
+    Cycle,
+}
+
+struct Graph {
+    nodes: Vec<String>,
+    missing: Vec<bool>,
+    edges: Vec<(Dependency, Highlight)>,
+}
+
+impl Graph {
+    fn new(template: &Template) -> Graph {
+        let dependencies = template.dependencies();
+        let cycles = template.cycles();
+        let violations = template.order_violations();
+        let mut nodes = template.columns();
+        nodes.extend(template.attributes.keys().filter(|name| !nodes.contains(name)).cloned().collect::<Vec<_>>());
+        for dependency in &dependencies {
+            for name in &[&dependency.from, &dependency.to] {
+                if !nodes.contains(name) {
+                    nodes.push(name.to_string());
+                }
+            }
+        }
+        let missing = nodes.iter().map(|name| !template.attributes.contains_key(name)).collect();
+        let edges = dependencies
+            .into_iter()
+            .map(|dependency| {
+                let highlight = if dependency.kind != DependencyKind::Rename &&
+                    cycles.iter().any(|cycle| cycle.contains(&dependency.from) && cycle.contains(&dependency.to))
+                {
+                    Highlight::Cycle
+                } else if violations.contains(&dependency) {
+                    Highlight::OrderViolation
+                } else {
+                    Highlight::Normal
+                };
+                (dependency, highlight)
+            })
+            .collect();
+        Graph { nodes, missing, edges }
+    }
+
+    fn node(&self, name: &str) -> usize {
+        self.nodes.iter().position(|node| node == name).unwrap()
+    }
+}
+
+fn escape_dot(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn escape_mermaid(s: &str) -> String {
+    s.replace('"', "#quot;")
+}
+
+impl Template {
+    ///The attribute dependency graph for Graphviz, arrows point from what's needed to what needs it
+    ///
+    ///Cycles are red and dependencies on attributes generated later are orange
+    pub fn dot(&self) -> String {
+        let graph = Graph::new(self);
+        let mut lines = vec!["digraph template {".to_string()];
+        for (name, &missing) in graph.nodes.iter().zip(&graph.missing) {
+            let style = if missing { " [style=dashed]" } else { "" };
+            lines.push(format!("    \"{}\"{};", escape_dot(name), style));
+        }
+        for &(ref dependency, highlight) in &graph.edges {
+            let mut attributes = vec![format!("label=\"{}\"", escape_dot(&dependency.label))];
+            match dependency.kind {
+                DependencyKind::Requires => {}
+                DependencyKind::Reuse | DependencyKind::Rename => attributes.push("style=dashed".to_string()),
+                DependencyKind::Copy => attributes.push("style=dotted".to_string()),
+            }
+            match highlight {
+                Highlight::Normal => {}
+                Highlight::OrderViolation => attributes.push("color=orange, penwidth=2".to_string()),
+                Highlight::Cycle => attributes.push("color=red, penwidth=2".to_string()),
+            }
+            lines.push(format!(
+                "    \"{}\" -> \"{}\" [{}];",
+                escape_dot(&dependency.from),
+                escape_dot(&dependency.to),
+                attributes.join(", ")
+            ));
+        }
+        lines.push("}".to_string());
+        lines.join("\n")
+    }
+
+    ///The attribute dependency graph for Mermaid, highlighted the same as `dot`
+    pub fn mermaid(&self) -> String {
+        let graph = Graph::new(self);
+        let mut lines = vec!["graph LR".to_string()];
+        for (i, (name, &missing)) in graph.nodes.iter().zip(&graph.missing).enumerate() {
+            if missing {
+                lines.push(format!("    n{}([\"{}\"])", i, escape_mermaid(name)));
+            } else {
+                lines.push(format!("    n{}[\"{}\"]", i, escape_mermaid(name)));
+            }
+        }
+        let mut styles = Vec::new();
+        for (i, &(ref dependency, highlight)) in graph.edges.iter().enumerate() {
+            let arrow = match dependency.kind {
+                DependencyKind::Requires | DependencyKind::Copy => "-->",
+                DependencyKind::Reuse | DependencyKind::Rename => "-.->",
+            };
+            lines.push(format!(
+                "    n{} {}|\"{}\"| n{}",
+                graph.node(&dependency.from),
+                arrow,
+                escape_mermaid(&dependency.label),
+                graph.node(&dependency.to)
+            ));
+            match highlight {
+                Highlight::Normal => {}
+                Highlight::OrderViolation => styles.push(format!("    linkStyle {} stroke:orange,stroke-width:2px", i)),
+                Highlight::Cycle => styles.push(format!("    linkStyle {} stroke:red,stroke-width:2px", i)),
+            }
+        }
+        lines.extend(styles);
+        lines.join("\n")
+    }
+}
+
+#[test]
+fn test_graph() {
+    let template: Template = r#"{"order": ["color", "size", "shade", "mood"], "rename": {"hue": "shade"}, "attributes": {
+        "size": {"choose": ["small", "large"]},
+        "color": {"choose": {"red": {}, "blue": {"requires": ["size:large"]}}},
+        "shade": {"copy": "color"},
+        "mood": {"choose": {"happy": {"requires": ["calm:yes"]}}, "requires": ["mood:happy"]}
+    }}"#.parse().unwrap();
+    assert_eq!(template.cycles(), vec![vec!["mood".to_string()]]);
+    assert_eq!(
+        template.dot(),
+        r#"digraph template {
+    "color";
+    "size";
+    "shade";
+    "mood";
+    "calm" [style=dashed];
+    "hue" [style=dashed];
+    "size" -> "color" [label="blue: size:large", color=orange, penwidth=2];
+    "color" -> "shade" [label="copy", style=dotted];
+    "mood" -> "mood" [label="mood:happy", color=red, penwidth=2];
+    "calm" -> "mood" [label="happy: calm:yes"];
+    "hue" -> "shade" [label="rename", style=dashed];
+}"#
+    );
+    let mermaid = template.mermaid();
+    assert!(mermaid.starts_with("graph LR\n    n0[\"color\"]\n"));
+    assert!(mermaid.contains("    n4([\"calm\"])\n"));
+    assert!(mermaid.contains("    n1 -->|\"blue: size:large\"| n0\n"));
+    assert!(mermaid.ends_with("    linkStyle 0 stroke:orange,stroke-width:2px\n    linkStyle 2 stroke:red,stroke-width:2px"));
+}
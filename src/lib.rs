@@ -1,41 +1,130 @@
 extern crate rand;
+extern crate rand_chacha;
 extern crate serde;
+#[macro_use]
 extern crate serde_json;
+extern crate serde_yaml;
+extern crate toml;
+extern crate ron;
 #[macro_use]
 extern crate serde_derive;
-#[cfg(any(target_arch = "wasm32", target_arch = "asmjs"))]
 #[macro_use]
-extern crate stdweb;
-#[cfg(any(target_arch = "wasm32", target_arch = "asmjs"))]
-extern crate yew;
+extern crate lazy_static;
+#[cfg(target_arch = "wasm32")]
+extern crate wasm_bindgen;
+#[cfg(target_arch = "wasm32")]
+extern crate serde_wasm_bindgen;
+#[cfg(all(test, target_arch = "wasm32"))]
+extern crate wasm_bindgen_test;
 
-#[cfg(any(target_arch = "wasm32", target_arch = "asmjs"))]
-mod web;
+#[cfg(target_arch = "wasm32")]
+pub mod web;
 
 mod serde_support;
+mod registry;
+mod mutation;
+mod formatting;
+mod locale;
+mod sheet;
+mod export;
+mod formats;
+mod schema;
+mod lint;
+mod diff;
+mod dependencies;
+mod graph;
+pub mod ffi;
+
+pub use registry::TemplateRegistry;
+pub use mutation::{Mutations, MUTATION};
+pub use formatting::{Filter, Formatting, register_filter};
+pub use locale::Locale;
+pub use formats::{Format, convert};
+pub use export::write_delimited;
+pub use schema::schema;
+pub use lint::{Problem, lint_dir};
+pub use diff::{Change, diff};
+pub use dependencies::{Dependency, DependencyKind};
+pub use rand_chacha::ChaChaRng;
 
 use std::collections::{BTreeMap, HashMap};
+use std::collections::hash_map::Entry;
 use std::ops::AddAssign;
 use std::str::FromStr;
 use std::fmt::{Display, Formatter};
-use rand::Rng;
-use rand::distributions::{WeightedChoice, Weighted, Distribution};
+use std::sync::{Mutex, MutexGuard};
+use rand::{Rng, SeedableRng};
+use rand::distributions::{WeightedIndex, Distribution};
 
 type Denied = BTreeMap<String, Vec<String>>;
 type Attributes = BTreeMap<String, Attribute>;
 type Generated = HashMap<String, String>;
+///Keys of the options chosen on the way to each generated value, including the value itself
+pub type Trace = BTreeMap<String, Vec<String>>;
+
+lazy_static! {
+    ///Templates registered at runtime through the C and JavaScript APIs, starting with the built in ones
+    static ref TEMPLATES: Mutex<TemplateRegistry> = {
+        let mut registry = TemplateRegistry::new();
+        registry.add("base", include_str!("../assets/base.json")).unwrap();
+        registry.add("obj", include_str!("../assets/obj.json")).unwrap();
+        registry.set_mutations(include_str!("../assets/mutations.json").parse().unwrap());
+        registry.resolve().unwrap();
+        Mutex::new(registry)
+    };
+}
+
+///A panic while it was locked can't have left the registry half changed, so poisoning is ignored
+fn templates() -> MutexGuard<'static, TemplateRegistry> {
+    TEMPLATES.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+///Random number generator for the `_with_rng` functions that gives the same character for the same seed
+///on every platform, as long as the version of morbitgen is the same
+pub fn seeded(seed: u64) -> ChaChaRng {
+    ChaChaRng::seed_from_u64(seed)
+}
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Clone)]
 pub struct Template {
+    ///templates to inherit from, later ones take precedence
+    #[serde(default, deserialize_with = "serde_support::one_or_many")]
+    pub extends: Vec<String>,
+    ///partial templates to mix in, later ones take precedence but parents are overridden by all of them
+    #[serde(default, deserialize_with = "serde_support::one_or_many")]
+    pub mixins: Vec<String>,
+    #[serde(default)]
     pub order: Vec<String>,
+    ///generate in `dependency_order` rather than the declared order, inherited by every child
+    #[serde(default)]
+    pub auto_order: bool,
+    ///inherited attributes to drop completely
+    #[serde(default)]
+    pub remove: Vec<String>,
+    ///inherited attributes to keep for `reuse` and `requires` but not generate
+    #[serde(default)]
+    pub disable: Vec<String>,
+    #[serde(default)]
     pub attributes: Attributes,
     #[serde(default)]
     pub rename: BTreeMap<String, String>,
     #[serde(default)]
     pub formatting: BTreeMap<String, String>,
+    ///display strings and formatting by language
+    #[serde(default)]
+    pub locales: BTreeMap<String, Locale>,
+    ///groups of attributes for character sheets
+    #[serde(default)]
+    pub sections: Vec<Section>,
 }
 
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Section {
+    pub name: String,
+    pub attributes: Vec<String>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
 pub struct Attribute {
     generator: Generator,
     ///completely replace parent attribute
@@ -44,9 +133,18 @@ pub struct Attribute {
     chance: Option<Chance>,
     ///requires for entire attribute
     requires: Vec<Requirement>,
+    ///inherited options to drop
+    remove: Vec<String>,
+    ///template this was defined in, only known after being resolved by a registry
+    origin: Option<String>,
+    ///key to use when this has to be merged into a choice as one alternative
+    alias: Option<String>,
+    ///text shown instead of the key
+    label: Option<String>,
+    description: Option<String>,
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Default, Deserialize, Serialize, Clone, PartialEq)]
 #[serde(rename_all = "lowercase")]
 enum Generator {
     ///Choose a key from the map using the value for the chance and requirements
@@ -56,16 +154,18 @@ enum Generator {
     ///Copy result of generator for attribute
     Same(String),
     ///Don't choose anything
+    #[default]
     Nothing,
 }
 
-#[derive(Debug, Deserialize, Serialize, Eq, PartialEq, Ord, PartialOrd, Copy, Clone)]
-enum Chance {
+#[derive(Debug, Default, Deserialize, Serialize, Eq, PartialEq, Ord, PartialOrd, Copy, Clone)]
+pub enum Chance {
     Never,
     ExtremelyRare,
     VeryRare,
     Rare,
     Uncommon,
+    #[default]
     Standard,
     Common,
     VeryCommon,
@@ -73,7 +173,7 @@ enum Chance {
     Always,
 }
 
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, PartialEq)]
 pub struct Requirement {
     pub possibilities: Vec<(String, String, bool)>,
 }
@@ -89,8 +189,8 @@ impl FromStr for Requirement {
             }
             let key = split[0].trim();
             let value = split[1].trim();
-            possibilities.push(if key.starts_with('!') {
-                (key[1..].to_string(), value.to_string(), true)
+            possibilities.push(if let Some(key) = key.strip_prefix('!') {
+                (key.to_string(), value.to_string(), true)
             } else {
                 (key.to_string(), value.to_string(), false)
             });
@@ -99,31 +199,31 @@ impl FromStr for Requirement {
     }
 }
 
+impl Requirement {
+    ///Whether a generated character meets this, options chosen on the way to a value count as well
+    pub fn met_by(&self, generated: &Generated, trace: &Trace) -> bool {
+        self.possibilities.is_empty()
+            || self.possibilities.iter().any(|(key, value, not)| match generated.get(key) {
+                Some(generated) => {
+                    let chosen = trace.get(key).is_some_and(|path| path.contains(value));
+                    *not ^ (value == "*" || generated == value || chosen)
+                }
+                None => *not,
+            })
+    }
+}
+
 impl Display for Requirement {
     fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
-        let mut count = 0;
-        for &(ref key, ref value, ref not) in &self.possibilities {
+        for (count, (key, value, not)) in self.possibilities.iter().enumerate() {
             let prefix = if count > 0 { "|" } else { "" };
             let deny = if *not { "!" } else { "" };
             write!(f, "{}{}{}:{}", prefix, deny, key, value)?;
-            count += 1;
         }
         Ok(())
     }
 }
 
-impl Default for Generator {
-    fn default() -> Self {
-        Generator::Nothing
-    }
-}
-
-impl Default for Chance {
-    fn default() -> Self {
-        Chance::Standard
-    }
-}
-
 impl Chance {
     pub fn chance(&self) -> u32 {
         use Chance::*;
@@ -145,7 +245,7 @@ impl Template {
     pub fn new(name: &str, parent: Option<&Template>) -> Template {
         use std::fs::File;
         use std::io::Read;
-        let mut json_file = File::open(&format!("assets/{}.json", name)).unwrap();
+        let mut json_file = File::open(format!("assets/{}.json", name)).unwrap();
         let mut contents = String::new();
         json_file.read_to_string(&mut contents).expect(
             "Unable to read file",
@@ -154,88 +254,348 @@ impl Template {
     }
 
     pub fn new_from_string(string: &str, parent: Option<&Template>) -> Template {
-        let mut template: Template = serde_json::from_str(string).unwrap();
-        if let Some(parent) = parent {
-            let mut order = parent.order.clone();
-            order.append(&mut template.order);
-            template.order = order;
-            let mut rename = parent.rename.clone();
-            rename.append(&mut template.rename);
-            template.rename = rename;
-            for (name, parent_attribute) in parent.attributes.clone() {
-                use std::collections::btree_map::Entry;
-                let name = template.rename.get(&name).map_or(name, |name| name.clone());
-                match template.attributes.entry(name) {
-                    Entry::Vacant(v) => {
-                        v.insert(parent_attribute);
-                    }
-                    Entry::Occupied(mut o) => {
-                        *o.get_mut() += parent_attribute;
-                    }
+        let mut template: Template = string.parse().unwrap();
+        match parent {
+            Some(parent) => template.inherit(parent),
+            None => template.apply_removals(),
+        }
+        if template.auto_order {
+            template.sort_order();
+        }
+        template
+    }
+
+    ///Merge a parent into this template, anything already in this template takes precedence
+    pub fn inherit(&mut self, parent: &Template) {
+        let mut order = parent.order.clone();
+        for name in self.order.drain(..) {
+            if !order.contains(&name) {
+                order.push(name);
+            }
+        }
+        self.order = order;
+        self.auto_order |= parent.auto_order;
+        let mut rename = parent.rename.clone();
+        rename.append(&mut self.rename);
+        self.rename = rename;
+        for (name, parent_attribute) in parent.attributes.clone() {
+            use std::collections::btree_map::Entry;
+            let name = self.rename.get(&name).map_or(name, |name| name.clone());
+            match self.attributes.entry(name) {
+                Entry::Vacant(v) => {
+                    v.insert(parent_attribute);
+                }
+                Entry::Occupied(mut o) => {
+                    *o.get_mut() += parent_attribute;
                 }
             }
         }
-        template
+        for (name, formatting) in &parent.formatting {
+            self.formatting.entry(name.clone()).or_insert_with(|| formatting.clone());
+        }
+        let mut sections = parent.sections.clone();
+        for section in self.sections.drain(..) {
+            match sections.iter_mut().find(|parent_section| parent_section.name == section.name) {
+                Some(parent_section) => parent_section.attributes.extend(section.attributes),
+                None => sections.push(section),
+            }
+        }
+        for section in &mut sections {
+            for attribute in &mut section.attributes {
+                if let Some(name) = self.rename.get(attribute) {
+                    *attribute = name.clone();
+                }
+            }
+        }
+        self.sections = sections;
+        for (language, parent_locale) in &parent.locales {
+            self.locales
+                .entry(language.clone())
+                .or_default()
+                .inherit(parent_locale, &self.rename);
+        }
+        self.apply_removals();
+    }
+
+    ///Drop the attributes in `remove` and stop generating the ones in `disable`, inherited or not
+    fn apply_removals(&mut self) {
+        for name in &self.remove {
+            self.attributes.remove(name);
+        }
+        let (rename, remove, disable) = (&self.rename, &self.remove, &self.disable);
+        self.order.retain(|name| {
+            let name = rename.get(name).unwrap_or(name);
+            !remove.contains(name) && !disable.contains(name)
+        });
     }
+
+    ///Mark every attribute and option that doesn't know where it came from yet as coming from `origin`
+    pub fn set_origin(&mut self, origin: &str) {
+        for attribute in self.attributes.values_mut() {
+            attribute.set_origin(origin);
+        }
+    }
+
+    ///The template as json that can be loaded again, optionally with where each attribute and option came from
+    pub fn dump(&self, annotated: bool) -> String {
+        let mut template = self.clone();
+        if !annotated {
+            for attribute in template.attributes.values_mut() {
+                attribute.clear_origin();
+            }
+        }
+        serde_json::to_string_pretty(&template).unwrap()
+    }
+
     pub fn generate<I>(&self, presets: I) -> Generated
     where
         I: IntoIterator<Item = Requirement>,
+    {
+        self.generate_traced(presets).0
+    }
+
+    ///Generate and also return which options were chosen to get each value
+    pub fn generate_traced<I>(&self, presets: I) -> (Generated, Trace)
+    where
+        I: IntoIterator<Item = Requirement>,
+    {
+        self.generate_traced_with_rng(presets, &mut rand::thread_rng())
+    }
+
+    ///Generate using `random` for every choice, so a seeded generator gives the same character each time
+    pub fn generate_with_rng<I, R>(&self, presets: I, random: &mut R) -> Generated
+    where
+        I: IntoIterator<Item = Requirement>,
+        R: Rng,
+    {
+        self.generate_traced_with_rng(presets, random).0
+    }
+
+    ///Order entries that aren't attributes and presets that can't be met are skipped, `undefined` and
+    ///`Requirement::met_by` find them
+    pub fn generate_traced_with_rng<I, R>(&self, presets: I, random: &mut R) -> (Generated, Trace)
+    where
+        I: IntoIterator<Item = Requirement>,
+        R: Rng,
     {
         let order = &self.order;
         let attributes = &self.attributes;
         let rename = &self.rename;
         let mut generated = Default::default();
         let mut denied = Default::default();
+        let mut trace = Default::default();
         add_requirements(
-            &presets.into_iter().collect(),
+            presets.into_iter().collect(),
             &mut generated,
             &mut denied,
+            &mut trace,
             attributes,
+            random,
         );
 
         for name in order {
             let name = rename.get(name).unwrap_or(name);
             if let Some(attribute) = attributes.get(name) {
-                attribute.generate(name, &mut generated, &mut denied, attributes);
-            } else {
-                println!("{} doesn't exist", name);
+                attribute.generate(name, &mut generated, &mut denied, &mut trace, attributes, random);
             }
         }
 
-        generated
+        (generated, trace)
+    }
+
+    ///Generate one attribute again keeping everything else, its options are still limited by the other values
+    ///and by the presets naming it
+    pub fn reroll<I, R>(&self, generated: &mut Generated, trace: &mut Trace, name: &str, presets: I, random: &mut R) -> Result<(), String>
+    where
+        I: IntoIterator<Item = Requirement>,
+        R: Rng,
+    {
+        let attribute = self.attributes.get(name).ok_or_else(|| format!("{} doesn't exist", name))?;
+        generated.remove(name);
+        trace.remove(name);
+        let presets = presets
+            .into_iter()
+            .filter(|preset| preset.possibilities.iter().any(|(key, _, _)| key == name))
+            .collect();
+        let mut denied = Default::default();
+        add_requirements(presets, generated, &mut denied, trace, &self.attributes, random);
+        attribute.generate(name, generated, &mut denied, trace, &self.attributes, random);
+        Ok(())
+    }
+
+    ///One line per generated attribute showing the options chosen to get to its value
+    pub fn explain(&self, generated: &Generated, trace: &Trace) -> String {
+        let mut lines = Vec::new();
+        for name in &self.order {
+            let name = self.rename.get(name).unwrap_or(name);
+            if let Some(value) = generated.get(name) {
+                match trace.get(name) {
+                    Some(path) if path.last() == Some(value) => {
+                        lines.push(format!("{}: {}", name, path.join(" > ")))
+                    }
+                    Some(path) => lines.push(format!("{}: {} > {}", name, path.join(" > "), value)),
+                    None => lines.push(format!("{}: {} (preset)", name, value)),
+                }
+            }
+        }
+        lines.join("\n")
     }
     
+    ///Format with one of the template's formatting entries, a formatting string, `json`, or `markdown` and
+    ///`html` for a character sheet
     pub fn format(&self, generated: &Generated, formatting: &str) -> Result<String, String> {
-        if formatting == "json" {
-            Ok(format!("{:#?}", generated))
-        } else {
-            let formatting: Formatting = self.formatting.get(formatting).unwrap_or(&formatting.to_string()).parse()?;
-            Ok(formatting.format(generated))
+        self.format_with(generated, formatting, &self.labels())
+    }
+
+    ///Format using the locale's formatting entry if it has one and its display strings for values
+    pub fn format_localized(&self, generated: &Generated, formatting: &str, language: &str) -> Result<String, String> {
+        self.format_with(generated, formatting, &self.locale(language)?)
+    }
+
+    ///Format as `format` does with display strings from `locale`, which is `labels()` or `locale()`. Get it
+    ///once to format several characters
+    pub fn format_with(&self, generated: &Generated, formatting: &str, locale: &Locale) -> Result<String, String> {
+        let formatting: Formatting = match locale.formatting.get(formatting) {
+            Some(formatting) => formatting.parse()?,
+            None if formatting == "json" => {
+                return serde_json::to_string_pretty(&generated.iter().collect::<BTreeMap<_, _>>()).map_err(|e| e.to_string())
+            }
+            None if formatting == "markdown" => return Ok(sheet::markdown(self, generated, locale)),
+            None if formatting == "html" => return Ok(sheet::html(self, generated, locale)),
+            None => self.formatting(formatting)?,
+        };
+        Ok(formatting.format_with(generated, Some(locale)))
+    }
+
+    ///A language's locale with anything it is missing filled in from `labels()`
+    pub fn locale(&self, language: &str) -> Result<Locale, String> {
+        let mut locale = self.locales.get(language).ok_or_else(|| format!("No locale for {}", language))?.clone();
+        locale.inherit(&self.labels(), &Default::default());
+        Ok(locale)
+    }
+
+    ///Labels and descriptions of attributes and options as a locale, including options of attributes that
+    ///get reused or copied
+    pub fn labels(&self) -> Locale {
+        let mut labels = Locale {
+            english: true,
+            ..Default::default()
+        };
+        for (name, attribute) in &self.attributes {
+            if let Some(ref label) = attribute.label {
+                labels.attributes.insert(name.clone(), label.clone());
+            }
+            if let Some(ref description) = attribute.description {
+                labels.attribute_descriptions.insert(name.clone(), description.clone());
+            }
+            let mut values = BTreeMap::new();
+            let mut descriptions = BTreeMap::new();
+            attribute.generator.labels(&mut values, &mut descriptions, &self.attributes, &mut vec![name.as_str()]);
+            if !values.is_empty() {
+                labels.values.insert(name.clone(), values);
+            }
+            if !descriptions.is_empty() {
+                labels.descriptions.insert(name.clone(), descriptions);
+            }
         }
+        labels
     }
     
+    ///Every value an attribute can end up with, including values of attributes it reuses or copies
+    pub fn values(&self, name: &str) -> Vec<String> {
+        let mut values = Vec::new();
+        if let Some(attribute) = self.attributes.get(name) {
+            attribute.generator.values(&mut values, &self.attributes, &mut vec![name]);
+        }
+        values
+    }
+
+    ///Parse one of the template's formatting entries, or the string itself if there is no entry with that name
+    pub fn formatting(&self, formatting: &str) -> Result<Formatting, String> {
+        self.formatting.get(formatting).map_or(formatting, |formatting| formatting.as_str()).parse()
+    }
+
+    ///Names in the order that aren't attributes, generating skips them
+    pub fn undefined(&self) -> Vec<&String> {
+        self.order
+            .iter()
+            .map(|name| self.rename.get(name).unwrap_or(name))
+            .filter(|name| !self.attributes.contains_key(*name))
+            .collect()
+    }
+
     pub fn always(&self, name: &str, value: &str) -> bool {
-        self.attributes.get(name).map_or(false, |attribute| attribute.generator.always(value, &self.attributes))
+        self.attributes.get(name).is_some_and(|attribute| attribute.generator.always(value, &self.attributes))
+    }
+}
+
+impl FromStr for Template {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Format::Json.parse(s)
     }
 }
 
 impl Attribute {
-    pub fn generate(
+    pub fn generate<R: Rng>(
         &self,
         name: &str,
         generated: &mut Generated,
         denied: &mut Denied,
+        trace: &mut Trace,
         attributes: &Attributes,
+        random: &mut R,
     ) {
         let mut valid = true;
         for requirement in &self.requires {
-            valid &= meets_requirement(requirement, &generated, &denied);
+            valid &= meets_requirement(requirement, generated, denied, trace);
         }
         if !valid {
             return;
         }
 
-        self.generator.generate(name, generated, denied, attributes);
+        self.generator.generate(name, generated, denied, trace, attributes, random);
+    }
+
+    ///Key for this attribute as an alternative in a choice, from its alias, the template it came from or
+    ///what it refers to. Otherwise it is `fallback`, which is `own` for the child's generator and
+    ///`inherited` for the parent's, with a number added if the name is already taken
+    fn alternative_name(&self, fallback: &str) -> String {
+        if let Some(ref alias) = self.alias {
+            return alias.clone();
+        }
+        if let Some(ref origin) = self.origin {
+            return origin.clone();
+        }
+        match self.generator {
+            Generator::Reuse(ref attribute_name) | Generator::Same(ref attribute_name) => attribute_name.clone(),
+            _ => fallback.to_string(),
+        }
+    }
+
+    ///Whether both are the same generator from the same template, as when a diamond inherits it twice
+    fn same_alternative(&self, other: &Attribute) -> bool {
+        self.origin == other.origin && self.generator == other.generator
+    }
+
+    fn set_origin(&mut self, origin: &str) {
+        if self.origin.is_none() {
+            self.origin = Some(origin.to_string());
+        }
+        if let Generator::Choose(ref mut choices) = self.generator {
+            for choice in choices.values_mut() {
+                choice.set_origin(origin);
+            }
+        }
+    }
+
+    fn clear_origin(&mut self) {
+        self.origin = None;
+        if let Generator::Choose(ref mut choices) = self.generator {
+            for choice in choices.values_mut() {
+                choice.clear_origin();
+            }
+        }
     }
 
     fn get_requirements(&self, name: &str, attributes: &Attributes) -> Vec<Requirement> {
@@ -254,15 +614,29 @@ impl AddAssign for Attribute {
         }
         let chance = &self.chance;
 
-        self.requires.append(&mut rhs.requires.clone());
+        for requirement in rhs.requires {
+            if !self.requires.contains(&requirement) {
+                self.requires.push(requirement);
+            }
+        }
+        //nothing of our own to generate, so use the parent's
+        if let Nothing = self.generator {
+            if let Choose(_) = rhs.generator {
+                self.generator = Choose(BTreeMap::new());
+            } else {
+                self.generator = rhs.generator;
+                return;
+            }
+        }
         match (&mut self.generator, rhs.generator) {
             (&mut Choose(ref mut choices), Choose(ref parent_choices)) => {
                 for (key, parent_value) in parent_choices {
+                    if self.remove.contains(key) {
+                        continue;
+                    }
                     if choices.contains_key(key) {
-                        let value = choices.get_mut(key).unwrap();
-                        if !value.replace {
-                            value.requires.append(&mut parent_value.requires.clone());
-                        }
+                        //options in both are merged the same way as attributes
+                        *choices.get_mut(key).unwrap() += parent_value.clone();
                     } else {
                         let mut value = parent_value.clone();
                         if let Some(chance) = self.chance {
@@ -272,60 +646,71 @@ impl AddAssign for Attribute {
                     }
                 }
             }
-            (&mut Choose(ref mut choices), ref parent) => {
-                let mut generator_name = "gen0".to_string();
-                for i in 1.. {
-                    if choices.contains_key(&generator_name) {
-                        generator_name = format!("gen{}", i);
-                    } else {
-                        break;
-                    }
+            //nothing to add from the parent
+            (_, Nothing) => {}
+            (&mut Choose(ref mut choices), parent) => {
+                let parent = Attribute {
+                    generator: parent,
+                    chance: *chance,
+                    origin: rhs.origin,
+                    alias: rhs.alias,
+                    ..Default::default()
+                };
+                //inherited twice through a diamond
+                if choices.values().any(|choice| choice.same_alternative(&parent)) {
+                    return;
                 }
-                choices.insert(
-                    generator_name,
-                    Attribute {
-                        generator: parent.clone(),
-                        chance: *chance,
-                        ..Default::default()
-                    },
-                );
+                let name = unique_name(choices, parent.alternative_name("inherited"));
+                choices.insert(name, parent);
             }
-            (&mut Nothing, Nothing) => {}
             (generator, parent) => {
+                if *generator == parent && self.origin == rhs.origin {
+                    return;
+                }
                 let mut choices = BTreeMap::new();
-                choices.insert(
-                    "gen0".to_string(),
-                    Attribute {
-                        generator: generator.clone(),
-                        ..Default::default()
-                    },
-                );
-                choices.insert(
-                    "gen1".to_string(),
-                    Attribute {
-                        generator: parent,
-                        chance: *chance,
-                        ..Default::default()
-                    },
-                );
+                let own = Attribute {
+                    generator: generator.clone(),
+                    origin: self.origin.clone(),
+                    alias: self.alias.clone(),
+                    ..Default::default()
+                };
+                choices.insert(own.alternative_name("own"), own);
+                let parent = Attribute {
+                    generator: parent,
+                    chance: *chance,
+                    origin: rhs.origin,
+                    alias: rhs.alias,
+                    ..Default::default()
+                };
+                let name = unique_name(&choices, parent.alternative_name("inherited"));
+                choices.insert(name, parent);
                 *generator = Choose(choices);
             }
         }
     }
 }
 
+///Add a number to the end of `name` until it isn't already a choice
+fn unique_name(choices: &Attributes, name: String) -> String {
+    if !choices.contains_key(&name) {
+        return name;
+    }
+    (2..).map(|i| format!("{} {}", name, i)).find(|name| !choices.contains_key(name)).unwrap()
+}
+
 impl Generator {
-    pub fn generate(
+    pub fn generate<R: Rng>(
         &self,
         name: &str,
         generated: &mut Generated,
         denied: &mut Denied,
+        trace: &mut Trace,
         attributes: &Attributes,
+        random: &mut R,
     ) {
         use Generator::*;
         match *self {
             Choose(ref options) => {
-                let mut random = rand::thread_rng();
                 if generated.contains_key(name) {
                     return;
                 }
@@ -334,7 +719,7 @@ impl Generator {
                     if !value.requires.is_empty() {
                         let mut valid = true;
                         for requirement in &value.requires {
-                            valid &= meets_requirement(requirement, &generated, &denied);
+                            valid &= meets_requirement(requirement, generated, denied, trace);
                         }
                         if !valid {
                             continue;
@@ -360,29 +745,22 @@ impl Generator {
                     } else if chance != Chance::Never {
                         choices
                             .entry(chance)
-                            .or_insert_with(Default::default)
+                            .or_default()
                             .push(option.clone());
                     }
                 }
                 if !choices.is_empty() {
-                    let mut vec: Vec<_> = choices
-                        .keys()
-                        .map(|chance| {
-                            Weighted {
-                                weight: chance.chance(),
-                                item: *chance,
-                            }
-                        })
-                        .collect();
-                    let wc = WeightedChoice::new(&mut vec);
-                    let vec = choices.remove(&wc.sample(&mut random)).unwrap();
-                    let option = &vec[random.gen_range(0, vec.len())];
+                    let chances: Vec<Chance> = choices.keys().cloned().collect();
+                    let weights = WeightedIndex::new(chances.iter().map(|chance| chance.chance())).unwrap();
+                    let vec = choices.remove(&chances[weights.sample(random)]).unwrap();
+                    let option = &vec[random_index(random, vec.len())];
+                    trace.entry(name.to_string()).or_default().push(option.clone());
                     match &options[option].generator {
                         &Generator::Nothing => {
                             generated.insert(name.to_string(), option.clone());
                         }
                         generator => {
-                            generator.generate(name, generated, denied, attributes);
+                            generator.generate(name, generated, denied, trace, attributes, random);
                         }
                     }
                 }
@@ -393,12 +771,14 @@ impl Generator {
                         name,
                         generated,
                         denied,
+                        trace,
                         attributes,
+                        random,
                     );
                 }
             }
             Same(ref attribute_name) => {
-                if let Some(value) = generated.get(attribute_name).map(|value| value.clone()) {
+                if let Some(value) = generated.get(attribute_name).cloned() {
                     generated.insert(name.to_string(), value);
                 }
             }
@@ -407,8 +787,8 @@ impl Generator {
     }
 
     fn get_requirements(&self, name: &str, attributes: &Attributes) -> Vec<Requirement> {
-        match self {
-            &Generator::Choose(ref options) => {
+        match *self {
+            Generator::Choose(ref options) => {
                 if let Some(value) = options.get(name) {
                     value.get_requirements(name, attributes)
                 } else {
@@ -420,31 +800,115 @@ impl Generator {
                     Vec::new()
                 }
             }
-            &Generator::Reuse(ref attribute_name) => {
+            Generator::Reuse(ref attribute_name) => {
                 if let Some(attribute) = attributes.get(attribute_name) {
                     attribute.generator.get_requirements(name, attributes)
                 } else {
                     Vec::new()
                 }
             }
-            &Generator::Same(ref attribute_name) => {
+            Generator::Same(ref attribute_name) => {
                 vec![
                     Requirement {
                         possibilities: vec![(attribute_name.to_string(), name.to_string(), false)],
                     },
                 ]
             }
-            &Generator::Nothing => Vec::new(),
+            Generator::Nothing => Vec::new(),
+        }
+    }
+
+    fn labels<'a>(
+        &'a self,
+        labels: &mut BTreeMap<String, String>,
+        descriptions: &mut BTreeMap<String, String>,
+        attributes: &'a Attributes,
+        visited: &mut Vec<&'a str>,
+    ) {
+        match *self {
+            Generator::Choose(ref options) => {
+                for (option, value) in options {
+                    if let Some(ref label) = value.label {
+                        labels.insert(option.clone(), label.clone());
+                    }
+                    if let Some(ref description) = value.description {
+                        descriptions.insert(option.clone(), description.clone());
+                    }
+                    value.generator.labels(labels, descriptions, attributes, visited);
+                }
+            }
+            Generator::Reuse(ref attribute_name) | Generator::Same(ref attribute_name) => {
+                if visited.contains(&attribute_name.as_str()) {
+                    return;
+                }
+                visited.push(attribute_name);
+                if let Some(attribute) = attributes.get(attribute_name) {
+                    attribute.generator.labels(labels, descriptions, attributes, visited);
+                }
+            }
+            Generator::Nothing => (),
+        }
+    }
+
+    fn values<'a>(&'a self, values: &mut Vec<String>, attributes: &'a Attributes, visited: &mut Vec<&'a str>) {
+        match *self {
+            Generator::Choose(ref options) => {
+                for (option, value) in options {
+                    match value.generator {
+                        Generator::Nothing => {
+                            if !values.contains(option) {
+                                values.push(option.clone());
+                            }
+                        }
+                        ref generator => generator.values(values, attributes, visited),
+                    }
+                }
+            }
+            Generator::Reuse(ref attribute_name) | Generator::Same(ref attribute_name) => {
+                if visited.contains(&attribute_name.as_str()) {
+                    return;
+                }
+                visited.push(attribute_name);
+                if let Some(attribute) = attributes.get(attribute_name) {
+                    attribute.generator.values(values, attributes, visited);
+                }
+            }
+            Generator::Nothing => (),
+        }
+    }
+
+    ///Keys of the options leading to the option `key` when it has a generator of its own, and that generator
+    fn alternative<'a>(&'a self, key: &str, attributes: &'a Attributes) -> Option<(Vec<String>, &'a Generator)> {
+        match *self {
+            Generator::Choose(ref options) => {
+                for (option, value) in options {
+                    if let Generator::Nothing = value.generator {
+                        continue;
+                    }
+                    if option == key {
+                        return Some((vec![option.clone()], &value.generator));
+                    }
+                    if let Some((mut path, generator)) = value.generator.alternative(key, attributes) {
+                        path.insert(0, option.clone());
+                        return Some((path, generator));
+                    }
+                }
+                None
+            }
+            Generator::Reuse(ref attribute_name) => {
+                attributes.get(attribute_name).and_then(|attribute| attribute.generator.alternative(key, attributes))
+            }
+            _ => None,
         }
     }
 
     fn contains(&self, name: &str, attributes: &Attributes) -> bool {
-        match self {
-            &Generator::Choose(ref options) => {
+        match *self {
+            Generator::Choose(ref options) => {
                 if let Some(value) = options.get(name) {
-                    match &value.generator {
-                        &Generator::Nothing => true,
-                        generator => generator.contains(name, attributes),
+                    match value.generator {
+                        Generator::Nothing => true,
+                        ref generator => generator.contains(name, attributes),
                     }
                 } else {
                     for value in options.values() {
@@ -455,29 +919,29 @@ impl Generator {
                     false
                 }
             }
-            &Generator::Reuse(ref attribute_name) |
-            &Generator::Same(ref attribute_name) => {
+            Generator::Reuse(ref attribute_name) |
+            Generator::Same(ref attribute_name) => {
                 if let Some(attribute) = attributes.get(attribute_name) {
                     attribute.generator.contains(name, attributes)
                 } else {
                     false
                 }
             }
-            &Generator::Nothing => false,
+            Generator::Nothing => false,
         }
     }
     
     fn always(&self, name: &str, attributes: &Attributes) -> bool {
-        match self {
-            &Generator::Choose(ref options) => {
+        match *self {
+            Generator::Choose(ref options) => {
                 if let Some(value) = options.get(name) {
-                    match &value.generator {
-                        &Generator::Nothing => value.chance.map_or(false, |chance| chance == Chance::Always) || options.len() == 1,
-                        generator => generator.always(name, attributes),
+                    match value.generator {
+                        Generator::Nothing => value.chance == Some(Chance::Always) || options.len() == 1,
+                        ref generator => generator.always(name, attributes),
                     }
                 } else {
                     for value in options.values() {
-                        if (value.chance.map_or(false, |chance| chance == Chance::Always) || options.len() == 1)
+                        if (value.chance == Some(Chance::Always) || options.len() == 1)
                             && value.generator.contains(name, attributes) {
                             return value.generator.always(name, attributes);
                         }
@@ -485,24 +949,31 @@ impl Generator {
                     false
                 }
             }
-            &Generator::Reuse(ref attribute_name) => {
+            Generator::Reuse(ref attribute_name) => {
                 if let Some(attribute) = attributes.get(attribute_name) {
                     attribute.generator.always(name, attributes)
                 } else {
                     false
                 }
             }
-            &Generator::Same(_) => true,
-            &Generator::Nothing => false,
+            Generator::Same(_) => true,
+            Generator::Nothing => false,
         }
     }
 }
 
-fn meets_requirement(requirement: &Requirement, generated: &Generated, denied: &Denied) -> bool {
+///Index below `len`, drawn as a u32 because a usize is drawn differently on 32 and 64 bit platforms
+fn random_index<R: Rng>(random: &mut R, len: usize) -> usize {
+    random.gen_range(0u32, len as u32) as usize
+}
+
+fn meets_requirement(requirement: &Requirement, generated: &Generated, denied: &Denied, trace: &Trace) -> bool {
     let mut matches = requirement.possibilities.is_empty();
     for &(ref key, ref value, not) in &requirement.possibilities {
         if generated.contains_key(key) {
-            matches |= not ^ (value == "*" || &generated[key] == value);
+            //options chosen on the way to the value count as well
+            let chosen = trace.get(key).is_some_and(|path| path.contains(value));
+            matches |= not ^ (value == "*" || &generated[key] == value || chosen);
         } else if not {
             if value == "*" {
                 matches = true;
@@ -515,159 +986,195 @@ fn meets_requirement(requirement: &Requirement, generated: &Generated, denied: &
     matches
 }
 
-fn add_requirements(
-    requires: &Vec<Requirement>,
+fn add_requirements<R: Rng>(
+    mut requires: Vec<Requirement>,
     generated: &mut Generated,
     denied: &mut Denied,
+    trace: &mut Trace,
     attributes: &Attributes,
+    random: &mut R,
 ) {
-    let mut requires = requires.clone();
     let mut delayed = Vec::new();
-    let mut random = rand::thread_rng();
     while let Some(requirement) = requires.pop().or_else(|| delayed.pop()) {
-        if !meets_requirement(&requirement, generated, denied) {
+        if !meets_requirement(&requirement, generated, denied, trace) {
             if requirement.possibilities.len() > 1 && !requires.is_empty() {
                 delayed.push(requirement);
                 continue;
             }
             let mut possibilities = requirement.possibilities.clone();
             let mut finding = true;
-            while finding && possibilities.len() > 0 {
-                let index = random.gen_range(0, possibilities.len());
+            while finding && !possibilities.is_empty() {
+                let index = random_index(random, possibilities.len());
                 let (key, value, not) = possibilities.remove(index);
+                let attribute = attributes.get(&key);
+                let alternative = attribute.and_then(|attribute| attribute.generator.alternative(&value, attributes));
                 if not && (!generated.contains_key(&key) || generated[&key] != value) {
-                    denied.entry(key).or_insert_with(Default::default).push(
-                        value,
-                    );
+                    denied.entry(key).or_default().push(value);
+                    finding = false;
+                } else if let (Some((path, generator)), false) = (alternative, generated.contains_key(&key)) {
+                    //an alternative isn't a value itself, so pick one under it
+                    trace.insert(key.clone(), path);
+                    generator.generate(&key, generated, denied, trace, attributes, random);
+                    match generated.get(&key) {
+                        Some(value) => requires.append(&mut attribute.unwrap().get_requirements(value, attributes)),
+                        None => {
+                            trace.remove(&key);
+                        }
+                    }
                     finding = false;
-                } else if !generated.contains_key(&key) {
-                    if let Some(attribute) = attributes.get(&key) {
+                } else if let Entry::Vacant(entry) = generated.entry(key) {
+                    if let Some(attribute) = attribute {
                         requires.append(&mut attribute.get_requirements(&value, attributes));
                     }
-                    generated.insert(key, value);
+                    entry.insert(value);
                     finding = false;
                 }
             }
-            if finding {
-                println!("Unable to find valid possibility for {:#?}", requirement);
-            }
         }
     }
 }
 
-#[derive(Clone, Debug)]
-pub struct Formatting {
-    requirement: Requirement,
-    contents: Vec<SubFormatting>,
-}
+#[test]
+fn test_remove() {
+    let parent = Template::new_from_string(r#"{"order": ["hair length", "hair style", "eyes"], "attributes": {
+        "hair length": {"choose": {"no": {}, "short": {}}},
+        "hair style": {"choose": {"straight": {}}},
+        "eyes": {"choose": {"round": {}, "almond": {}}}
+    }}"#, None);
+    let child = Template::new_from_string(r#"{"remove": ["hair length"], "disable": ["hair style"], "attributes": {
+        "eyes": {"remove": ["almond"]}
+    }}"#, Some(&parent));
+    assert_eq!(child.order, vec!["eyes"]);
+    assert!(!child.attributes.contains_key("hair length"));
+    assert!(child.attributes.contains_key("hair style"));
+    assert!(child.always("eyes", "round"));
 
-#[derive(Clone, Debug)]
-enum SubFormatting {
-    Text(String),
-    Formatted(Formatting),
-    Variable(String),
+    let template = Template::new_from_string(r#"{"order": ["hair length", "eyes"], "remove": ["hair length"], "disable": ["eyes"], "attributes": {
+        "hair length": {"choose": {"no": {}}},
+        "eyes": {"choose": {"round": {}}}
+    }}"#, None);
+    assert!(template.order.is_empty());
+    assert!(!template.attributes.contains_key("hair length"));
 }
 
-impl Formatting {
-    pub fn format(&self, generated: &Generated) -> String {
-        if meets_requirement(&self.requirement, generated, &Default::default()) {
-            let mut output = String::new();
-            for formatting in &self.contents {
-                let string = match formatting {
-                    &SubFormatting::Text(ref text) => text.clone(),
-                    &SubFormatting::Variable(ref variable) => {
-                        let value = generated.get(&variable.to_lowercase()).map_or(
-                            "".to_string(),
-                            |value| value.clone(),
-                        );
-                        if &variable.to_uppercase() == variable {
-                            value.to_uppercase()
-                        } else if !value.is_empty() &&
-                                   variable.chars().next().map_or(false, |c| c.is_uppercase())
-                        {
-                            let mut chars = value.chars();
-                            chars.next().unwrap().to_uppercase().collect::<String>() +
-                                &chars.collect::<String>()
-                        } else {
-                            value
-                        }
-                    }
-                    &SubFormatting::Formatted(ref formatting) => formatting.format(generated),
-                };
-                if !string.is_empty() {
-                    output += &string;
-                }
-            }
-            for splitter in vec!["\n", " "].into_iter() {
-                output = output
-                    .split(splitter)
-                    .filter(|split| !split.is_empty())
-                    .map(|split| split.trim())
-                    .collect::<Vec<_>>()
-                    .join(splitter);
-            }
-            output.replace(" .", ".")
-        } else {
-            String::new()
-        }
+#[test]
+fn test_inherit_generator() {
+    let parent = Template::new_from_string(r#"{"order": ["size", "color", "shade"], "attributes": {
+        "size": {"choose": {"large": {}}},
+        "color": {"choose": {"red": {}, "blue": {}}},
+        "shade": {"reuse": "color"}
+    }}"#, None);
+    let child = Template::new_from_string(r#"{"attributes": {
+        "color": {"requires": ["size:large"]},
+        "shade": {"requires": ["size:large"]}
+    }}"#, Some(&parent));
+    match child.attributes["color"].generator {
+        Generator::Choose(ref choices) => assert_eq!(choices.keys().collect::<Vec<_>>(), vec!["blue", "red"]),
+        _ => panic!("color should still choose"),
+    }
+    match child.attributes["shade"].generator {
+        Generator::Reuse(ref name) => assert_eq!(name, "color"),
+        _ => panic!("shade should still reuse"),
     }
+    assert_eq!(child.attributes["shade"].requires.len(), 1);
 }
 
-impl FromStr for Formatting {
-    type Err = String;
+#[test]
+fn test_alternatives() {
+    let parent = Template::new_from_string(r#"{"order": ["color", "casing color", "shiny"], "attributes": {
+        "color": {"choose": {"red": {}}},
+        "casing color": {"choose": {"grey": {}}, "alias": "plain"},
+        "shiny": {"choose": {"yes": {}}, "requires": ["casing color:matching"]}
+    }}"#, None);
+    let child = Template::new_from_string(r#"{"attributes": {
+        "casing color": {"reuse": "color", "alias": "matching", "chance": "Never"}
+    }}"#, Some(&parent));
+    let (generated, trace) = child.generate_traced(Vec::new());
+    assert_eq!(generated["casing color"], "red");
+    assert_eq!(trace["casing color"], vec!["matching", "red"]);
+    assert_eq!(generated["shiny"], "yes");
+    assert_eq!(child.explain(&generated, &trace).lines().nth(1), Some("casing color: matching > red"));
+}
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut requirement = Default::default();
-        let mut contents = Vec::new();
-        let mut depth = 0;
-        let mut current = String::new();
-        let mut escape = false;
-        for c in s.chars() {
-            if escape {
-                escape = false;
-                current.push(c);
-                continue;
-            }
-            match c {
-                '?' if depth == 0 => {
-                    requirement = current.parse()?;
-                    current = String::new();
-                }
-                '[' => {
-                    if depth == 0 {
-                        contents.push(SubFormatting::Text(current));
-                        current = String::new();
-                    } else {
-                        current.push(c);
-                    }
-                    depth += 1;
-                }
-                ']' => {
-                    depth -= 1;
-                    if depth == 0 {
-                        if current.contains('?') {
-                            contents.push(SubFormatting::Formatted(current.parse()?));
-                        } else {
-                            contents.push(SubFormatting::Variable(current));
-                        }
-                        current = String::new();
-                    } else {
-                        current.push(c);
-                    }
-                }
-                '\\' => escape = true,
-                c => current.push(c),
-            }
-        }
-        contents.push(SubFormatting::Text(current));
-        Ok(Formatting {
-            requirement,
-            contents,
-        })
+#[test]
+fn test_alternative_names() {
+    let keys = |template: &Template, name: &str| match template.attributes[name].generator {
+        Generator::Choose(ref choices) => choices.keys().cloned().collect::<Vec<_>>(),
+        _ => panic!("{} should choose", name),
+    };
+    let mut parent = Template::new_from_string(r#"{"order": ["color", "shade", "tint"], "attributes": {
+        "color": {"choose": {"red": {}}},
+        "shade": {"choose": {"dark": {}}},
+        "tint": {"reuse": "color"}
+    }}"#, None);
+    let child = Template::new_from_string(r#"{"attributes": {
+        "shade": {"reuse": "color"},
+        "tint": {"copy": "color"}
+    }}"#, Some(&parent));
+    assert_eq!(keys(&child, "shade"), vec!["color", "inherited"]);
+    assert_eq!(keys(&child, "tint"), vec!["color", "color 2"]);
+
+    parent.set_origin("base");
+    let child = Template::new_from_string(r#"{"attributes": {"shade": {"reuse": "color"}}}"#, Some(&parent));
+    assert_eq!(keys(&child, "shade"), vec!["base", "color"]);
+}
+
+#[test]
+fn test_unmet() {
+    let template = Template::new_from_string(r#"{"order": ["color", "size"], "attributes": {
+        "color": {"choose": {"warm": {"choose": ["red"]}}}
+    }}"#, None);
+    assert_eq!(template.undefined(), vec!["size"]);
+    let (generated, trace) = template.generate_traced(vec!["color:red".parse().unwrap(), "color:blue".parse().unwrap()]);
+    let met = |preset: &str| preset.parse::<Requirement>().unwrap().met_by(&generated, &trace);
+    assert!(met("color:red") ^ met("color:blue"));
+    let (generated, trace) = template.generate_traced(Vec::new());
+    let met = |preset: &str| preset.parse::<Requirement>().unwrap().met_by(&generated, &trace);
+    assert!(met("color:warm") && met("!size:large") && met("size:small|color:*"));
+    assert!(!met("!color:red") && !met("size:large"));
+}
+
+#[test]
+fn test_alternative_preset() {
+    let template = Template::new_from_string(r#"{"order": ["color", "mood"], "attributes": {
+        "color": {"choose": {"warm": {"choose": {"red": {}, "hot": {"choose": ["orange"]}}}, "blue": {}}},
+        "mood": {"choose": {"angry": {"requires": ["color:hot"]}, "calm": {"requires": ["color:blue"]}}}
+    }}"#, None);
+    for _ in 0..10 {
+        let (generated, trace) = template.generate_traced(vec!["color:warm".parse().unwrap()]);
+        assert!(generated["color"] == "red" || generated["color"] == "orange");
+        assert_eq!(trace["color"][0], "warm");
+        assert!("color:warm".parse::<Requirement>().unwrap().met_by(&generated, &trace));
+        let (generated, trace) = template.generate_traced(vec!["color:hot".parse().unwrap()]);
+        assert_eq!(generated["color"], "orange");
+        assert_eq!(trace["color"], vec!["warm", "hot", "orange"]);
+        assert_eq!(generated["mood"], "angry");
     }
 }
 
 #[test]
-fn test_formatting() {
-    println!("{:#?}", "They have a [head casing] head casing [!eye shape:no?with [eye shape] eyes and [pupil] pupils]".parse::<Formatting>().unwrap());
+fn test_labels() {
+    let template = Template::new_from_string(r#"{"order": ["color", "casing color"], "attributes": {
+        "color": {"choose": {"rd": {"label": "red", "description": "like a fire truck"}}, "label": "Colour"},
+        "casing color": {"reuse": "color"}
+    }}"#, None);
+    let generated = template.generate(Vec::new());
+    assert_eq!(generated["casing color"], "rd");
+    assert_eq!(template.format(&generated, "[color] [casing color] [color|id]").unwrap(), "red red rd");
+    assert_eq!(template.labels().attribute("color"), "Colour");
+    assert_eq!(template.format(&generated, "[casing color|description|capitalize]").unwrap(), "Like a fire truck");
+}
+
+#[test]
+fn test_seeded() {
+    let template = Template::new_from_string(r#"{"order": ["color"], "attributes": {
+        "color": {"choose": {"red": {}, "blue": {"chance": "Rare"}, "green": {"chance": "Common"}, "grey": {}}}
+    }}"#, None);
+    let mut random = seeded(42);
+    let colors: Vec<String> = (0..6).map(|_| template.generate_with_rng(Vec::new(), &mut random)["color"].clone()).collect();
+    //seeds have to keep giving the same characters, so this only changes with a new version of morbitgen
+    assert_eq!(colors, vec!["green", "blue", "green", "green", "green", "grey"]);
+    let preset = || vec!["color:red|color:grey|color:blue".parse().unwrap()];
+    let colors: Vec<String> = (0..6).map(|_| template.generate_with_rng(preset(), &mut random)["color"].clone()).collect();
+    assert_eq!(colors, vec!["grey", "grey", "blue", "red", "blue", "grey"]);
 }
@@ -0,0 +1,398 @@
+use super::{Attribute, Attributes, Format, Formatting, Generator, Locale, Mutations, Template, TemplateRegistry};
+use super::mutation::MUTATION;
+use serde::de::{self, Deserialize, DeserializeSeed, Deserializer, MapAccess, SeqAccess, Visitor};
+use std::collections::BTreeMap;
+use std::fmt::{self, Display, Formatter};
+use std::path::{Path, PathBuf};
+
+///Something wrong with a template file, `line` is where the name involved first shows up in the file
+#[derive(Debug, Serialize, PartialEq)]
+pub struct Problem {
+    pub file: String,
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{}: {}", self.file, line, self.message),
+            None => write!(f, "{}: {}", self.file, self.message),
+        }
+    }
+}
+
+///A file that was read, to find lines in
+struct Source {
+    path: PathBuf,
+    contents: String,
+}
+
+impl Source {
+    fn problem(&self, name: Option<&str>, message: String) -> Problem {
+        Problem {
+            file: self.path.display().to_string(),
+            line: name.and_then(|name| lines_of(&self.contents, name).into_iter().next()),
+            message,
+        }
+    }
+}
+
+///Lines the name shows up on as a key in any of the formats, or anywhere as a string if it's never a key
+fn lines_of(contents: &str, name: &str) -> Vec<usize> {
+    let quoted = format!("\"{}\"", name);
+    let keys = [format!("{}:", quoted), format!("{} =", quoted), format!("{}:", name), format!("{} =", name)];
+    let lines = lines_where(contents, |line| {
+        let line = line.trim_start_matches(|c: char| c.is_whitespace() || c == '-' || c == '{' || c == ',');
+        keys.iter().any(|key| line.starts_with(key.as_str()))
+    });
+    if lines.is_empty() {
+        lines_where(contents, |line| line.contains(&quoted))
+    } else {
+        lines
+    }
+}
+
+fn lines_where<F: Fn(&str) -> bool>(contents: &str, matches: F) -> Vec<usize> {
+    contents.lines().enumerate().filter(|&(_, line)| matches(line)).map(|(i, _)| i + 1).collect()
+}
+
+///Check every template, mutation and locale in a directory, only failing if the directory can't be read
+pub fn lint_dir<P: AsRef<Path>>(path: P) -> Result<Vec<Problem>, String> {
+    use std::fs;
+    let path = path.as_ref();
+    let mut problems = Vec::new();
+    let mut sources: BTreeMap<String, Source> = BTreeMap::new();
+    let mut registry = TemplateRegistry::new();
+    let mut files = Vec::new();
+    for entry in fs::read_dir(path).map_err(|e| format!("{}: {}", path.display(), e))? {
+        files.push(entry.map_err(|e| e.to_string())?.path());
+    }
+    let locales = path.join("locales");
+    if locales.is_dir() {
+        for entry in fs::read_dir(&locales).map_err(|e| format!("{}: {}", locales.display(), e))? {
+            files.push(entry.map_err(|e| e.to_string())?.path());
+        }
+    }
+    files.sort();
+
+    for file in files {
+        let format = match Format::from_path(&file) {
+            Some(format) => format,
+            None => continue,
+        };
+        let source = match fs::read_to_string(&file) {
+            Ok(contents) => Source { path: file.clone(), contents },
+            Err(e) => {
+                problems.push(Problem { file: file.display().to_string(), line: None, message: e.to_string() });
+                continue;
+            }
+        };
+        for key in duplicate_keys(format, &source.contents) {
+            //the second time the key shows up is most likely the duplicate
+            let lines = lines_of(&source.contents, key.rsplit(" > ").next().unwrap());
+            problems.push(Problem {
+                file: file.display().to_string(),
+                line: lines.get(1).or(lines.first()).cloned(),
+                message: format!("duplicate key {}", key),
+            });
+        }
+        let name = file.file_stem().and_then(|name| name.to_str()).unwrap_or("").to_string();
+        if file.parent() == Some(locales.as_path()) {
+            match format.parse::<Locale>(&source.contents) {
+                Ok(locale) => lint_formatting(&locale.formatting, &source, &mut problems),
+                Err(e) => problems.push(source.problem(None, e)),
+            }
+        } else if name == "mutations" {
+            if let Err(e) = format.parse::<Mutations>(&source.contents) {
+                problems.push(source.problem(None, e));
+            }
+        } else {
+            match format.parse::<Template>(&source.contents) {
+                Ok(template) => {
+                    lint_names(&template.attributes, &source, &mut problems);
+                    lint_formatting(&template.formatting, &source, &mut problems);
+                    for locale in template.locales.values() {
+                        lint_formatting(&locale.formatting, &source, &mut problems);
+                    }
+                    registry.insert(&name, template);
+                    sources.insert(name, source);
+                }
+                Err(e) => problems.push(source.problem(None, e)),
+            }
+        }
+    }
+
+    if let Err(e) = registry.resolve() {
+        problems.push(Problem { file: path.display().to_string(), line: None, message: e });
+        return Ok(problems);
+    }
+    for (name, source) in &sources {
+        lint_template(name, &registry, source, &sources, &mut problems);
+    }
+    //inherited attributes get checked again for every child
+    let mut unique: Vec<Problem> = Vec::new();
+    for problem in problems {
+        if !unique.contains(&problem) {
+            unique.push(problem);
+        }
+    }
+    Ok(unique)
+}
+
+///Attribute and option names with whitespace around them or doubled up inside, unless a label is shown instead
+fn lint_names(attributes: &Attributes, source: &Source, problems: &mut Vec<Problem>) {
+    for (name, attribute) in attributes {
+        if (name.trim() != name || name.contains("  ")) && attribute.label.is_none() {
+            problems.push(source.problem(Some(name), format!("stray whitespace in \"{}\"", name)));
+        }
+        if let Generator::Choose(ref options) = attribute.generator {
+            lint_names(options, source, problems);
+        }
+    }
+}
+
+fn lint_formatting(formatting: &BTreeMap<String, String>, source: &Source, problems: &mut Vec<Problem>) {
+    for (name, string) in formatting {
+        if let Err(e) = string.parse::<Formatting>() {
+            problems.push(source.problem(Some(name), format!("formatting {}: {}", name, e)));
+        }
+    }
+}
+
+///Check a template after inheritance, only its own order and sections are checked and entries that
+///templates inheriting from it define are fine
+fn lint_template(
+    name: &str,
+    registry: &TemplateRegistry,
+    source: &Source,
+    sources: &BTreeMap<String, Source>,
+    problems: &mut Vec<Problem>,
+) {
+    let loaded = registry.source(name).unwrap();
+    let template = registry.get(name).unwrap();
+    let undefined = registry.undefined(name);
+    //problems with inherited attributes are reported where they were defined
+    let origin = |attribute: &Attribute| {
+        attribute.origin.as_ref().and_then(|origin| sources.get(origin)).unwrap_or(source)
+    };
+    for name in &loaded.order {
+        let renamed = template.rename.get(name).unwrap_or(name);
+        if undefined.contains(&renamed) {
+            problems.push(source.problem(Some(name), format!("{} is in the order but isn't an attribute", renamed)));
+        }
+    }
+    for section in &loaded.sections {
+        for entry in &section.attributes {
+            let renamed = template.rename.get(entry).unwrap_or(entry);
+            if !template.attributes.contains_key(renamed) && !registry.defined_below(name, renamed) {
+                problems.push(source.problem(
+                    Some(entry),
+                    format!("section {} has {} which isn't an attribute", section.name, renamed),
+                ));
+            }
+        }
+    }
+    for (name, attribute) in &template.attributes {
+        lint_attribute(template, name, attribute, &origin, problems);
+    }
+    for dependency in template.order_violations() {
+        let source = template.attributes.get(&dependency.to).map_or(source, |attribute| origin(attribute));
+        problems.push(source.problem(
+            Some(&dependency.to),
+            format!("{} depends on {} ({}) which is generated after it", dependency.to, dependency.from, dependency.label),
+        ));
+    }
+}
+
+fn lint_attribute<'a, F>(template: &'a Template, name: &str, attribute: &'a Attribute, origin: &F, problems: &mut Vec<Problem>)
+where
+    F: Fn(&'a Attribute) -> &'a Source,
+{
+    let source = origin(attribute);
+    for requirement in &attribute.requires {
+        for (key, _, _) in &requirement.possibilities {
+            let message = if key.is_empty() {
+                format!("{} requires {} which has no attribute", name, requirement)
+            } else if key == MUTATION {
+                continue;
+            } else if !template.attributes.contains_key(key) {
+                format!("{} requires {} which isn't an attribute", name, key)
+            } else {
+                continue;
+            };
+            problems.push(source.problem(Some(name), message));
+        }
+    }
+    match attribute.generator {
+        Generator::Choose(ref options) => {
+            for (option, value) in options {
+                lint_attribute(template, option, value, origin, problems);
+            }
+        }
+        Generator::Reuse(ref target) | Generator::Same(ref target) => {
+            if !template.attributes.contains_key(target) {
+                problems.push(source.problem(Some(name), format!("{} refers to {} which isn't an attribute", name, target)));
+            }
+        }
+        Generator::Nothing => {}
+    }
+}
+
+///Keys that show up more than once in the same map, which the parser would silently overwrite
+fn duplicate_keys(format: Format, contents: &str) -> Vec<String> {
+    let mut duplicates = Vec::new();
+    {
+        let seed = Duplicates { path: String::new(), duplicates: &mut duplicates };
+        //anything that fails to parse is reported by the real parser
+        let _ = match format {
+            Format::Json => seed.deserialize(&mut ::serde_json::Deserializer::from_str(contents)).map_err(|e| e.to_string()),
+            Format::Yaml => seed.deserialize(::serde_yaml::Deserializer::from_str(contents)).map_err(|e| e.to_string()),
+            Format::Toml => seed.deserialize(&mut ::toml::Deserializer::new(contents)).map_err(|e| e.to_string()),
+            Format::Ron => ::ron::de::Deserializer::from_str(contents)
+                .map_err(|e| e.to_string())
+                .and_then(|mut deserializer| seed.deserialize(&mut deserializer).map_err(|e| e.to_string())),
+        };
+    }
+    duplicates
+}
+
+struct Duplicates<'a> {
+    path: String,
+    duplicates: &'a mut Vec<String>,
+}
+
+impl<'de, 'a> DeserializeSeed<'de> for Duplicates<'a> {
+    type Value = ();
+
+    fn deserialize<D>(self, deserializer: D) -> Result<(), D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de, 'a> Visitor<'de> for Duplicates<'a> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut Formatter) -> fmt::Result {
+        formatter.write_str("anything")
+    }
+
+    fn visit_bool<E: de::Error>(self, _: bool) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_i64<E: de::Error>(self, _: i64) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_u64<E: de::Error>(self, _: u64) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_f64<E: de::Error>(self, _: f64) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_str<E: de::Error>(self, _: &str) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        self.deserialize(deserializer)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        while let Some(()) = seq.next_element_seed(Duplicates { path: self.path.clone(), duplicates: self.duplicates })? {}
+        Ok(())
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        let mut keys = Vec::new();
+        while let Some(key) = map.next_key::<Key>()? {
+            let path = format!("{}{}", self.path, key.0);
+            if keys.contains(&key.0) {
+                self.duplicates.push(path.clone());
+            } else {
+                keys.push(key.0);
+            }
+            map.next_value_seed(Duplicates { path: format!("{} > ", path), duplicates: self.duplicates })?;
+        }
+        Ok(())
+    }
+}
+
+///A map key of any type, as text
+struct Key(String);
+
+impl<'de> Deserialize<'de> for Key {
+    fn deserialize<D>(deserializer: D) -> Result<Key, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct KeyVisitor;
+        impl<'de> Visitor<'de> for KeyVisitor {
+            type Value = Key;
+
+            fn expecting(&self, formatter: &mut Formatter) -> fmt::Result {
+                formatter.write_str("a key")
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<Key, E> {
+                Ok(Key(value.to_string()))
+            }
+
+            fn visit_i64<E: de::Error>(self, value: i64) -> Result<Key, E> {
+                Ok(Key(value.to_string()))
+            }
+
+            fn visit_u64<E: de::Error>(self, value: u64) -> Result<Key, E> {
+                Ok(Key(value.to_string()))
+            }
+
+            fn visit_bool<E: de::Error>(self, value: bool) -> Result<Key, E> {
+                Ok(Key(value.to_string()))
+            }
+        }
+        deserializer.deserialize_any(KeyVisitor)
+    }
+}
+
+#[test]
+fn test_lint() {
+    use std::fs;
+    let dir = ::std::env::temp_dir().join(format!("morbitgen-lint-{}", ::std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("base.json"), r#"{"order": ["size", "color", "shade", "missing"], "attributes": {
+    "size": {"choose": {"small ": {}, "tiny ": {"label": "tiny"}, "large": {"requires": ["mood:happy", "shade:dark"]}}},
+    "shade": {"reuse": "tint"},
+    "shade": {"reuse": "tint"}
+}, "formatting": {"short": "[size"}, "sections": [{"name": "Looks", "attributes": ["size", "color", "wings"]}]}"#).unwrap();
+    fs::write(dir.join("child.yaml"), "extends: base\nattributes:\n  color:\n    choose: [blue]\n    requires: ['hue:light']\n").unwrap();
+    let problems: Vec<String> = lint_dir(&dir).unwrap().iter().map(|problem| problem.to_string()).collect();
+    fs::remove_dir_all(&dir).unwrap();
+    let base = dir.join("base.json").display().to_string();
+    let child = dir.join("child.yaml").display().to_string();
+    let expected = vec![
+        format!("{}:4: duplicate key attributes > shade", base),
+        format!("{}:2: stray whitespace in \"small \"", base),
+        format!("{}:5: formatting short: unclosed [ in \"[size\"", base),
+        format!("{}:1: missing is in the order but isn't an attribute", base),
+        format!("{}:5: section Looks has wings which isn't an attribute", base),
+        format!("{}:3: shade refers to tint which isn't an attribute", base),
+        format!("{}:2: large requires mood which isn't an attribute", base),
+        format!("{}:2: size depends on shade (large: shade:dark) which is generated after it", base),
+        format!("{}:3: color requires hue which isn't an attribute", child),
+    ];
+    assert_eq!(problems, expected);
+}
@@ -0,0 +1,100 @@
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+///Display strings and formatting entries for one language, anything missing is shown as it is in the template
+#[derive(Debug, Default, Deserialize, Serialize, Clone)]
+pub struct Locale {
+    ///whether English rules like `[a]` articles should be used, they are left out otherwise
+    #[serde(default)]
+    pub english: bool,
+    ///display names for attributes
+    #[serde(default)]
+    pub attributes: BTreeMap<String, String>,
+    ///display strings for options, by attribute
+    #[serde(default)]
+    pub values: BTreeMap<String, BTreeMap<String, String>>,
+    ///descriptions of attributes
+    #[serde(default)]
+    pub attribute_descriptions: BTreeMap<String, String>,
+    ///descriptions of options, by attribute
+    #[serde(default)]
+    pub descriptions: BTreeMap<String, BTreeMap<String, String>>,
+    ///formatting entries used instead of the template's ones
+    #[serde(default)]
+    pub formatting: BTreeMap<String, String>,
+}
+
+impl FromStr for Locale {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ::serde_json::from_str(s).map_err(|e| e.to_string())
+    }
+}
+
+impl Locale {
+    ///Display string for an option
+    pub fn value<'a>(&'a self, attribute: &str, value: &'a str) -> &'a str {
+        self.values
+            .get(attribute)
+            .and_then(|values| values.get(value))
+            .map_or(value, |value| value.as_str())
+    }
+
+    ///Display name for an attribute
+    pub fn attribute<'a>(&'a self, attribute: &'a str) -> &'a str {
+        self.attributes.get(attribute).map_or(attribute, |name| name.as_str())
+    }
+
+    ///Description of an option, or of the attribute itself if `value` is None
+    pub fn description(&self, attribute: &str, value: Option<&str>) -> Option<&str> {
+        match value {
+            Some(value) => self.descriptions.get(attribute).and_then(|descriptions| descriptions.get(value)),
+            None => self.attribute_descriptions.get(attribute),
+        }
+        .map(|description| description.as_str())
+    }
+
+    ///Fill in anything missing from the parent's locale, with the parent's attributes renamed
+    pub fn inherit(&mut self, parent: &Locale, rename: &BTreeMap<String, String>) {
+        let renamed = |name: &String| rename.get(name).unwrap_or(name).clone();
+        for (attribute, name) in &parent.attributes {
+            self.attributes.entry(renamed(attribute)).or_insert_with(|| name.clone());
+        }
+        for (attribute, values) in &parent.values {
+            let own = self.values.entry(renamed(attribute)).or_default();
+            for (value, display) in values {
+                own.entry(value.clone()).or_insert_with(|| display.clone());
+            }
+        }
+        for (attribute, description) in &parent.attribute_descriptions {
+            self.attribute_descriptions.entry(renamed(attribute)).or_insert_with(|| description.clone());
+        }
+        for (attribute, descriptions) in &parent.descriptions {
+            let own = self.descriptions.entry(renamed(attribute)).or_default();
+            for (value, description) in descriptions {
+                own.entry(value.clone()).or_insert_with(|| description.clone());
+            }
+        }
+        for (name, formatting) in &parent.formatting {
+            self.formatting.entry(name.clone()).or_insert_with(|| formatting.clone());
+        }
+    }
+}
+
+#[test]
+fn test_locale() {
+    use super::Template;
+    let parent = Template::new_from_string(r#"{"order": ["color", "hands"], "attributes": {
+        "color": {"choose": {"red": {}}},
+        "hands": {"choose": {"claws": {}}}
+    }, "formatting": {"short": "[Color] [hands]"}, "locales": {"fr": {"values": {"hands": {"claws": "griffes"}}}}}"#, None);
+    let child = Template::new_from_string(r#"{"rename": {"hands": "wings"}, "attributes": {}, "locales": {
+        "fr": {"values": {"color": {"red": "rouge"}}, "formatting": {"short": "[wings] [color]"}},
+        "en": {"english": true}
+    }}"#, Some(&parent));
+    let generated = child.generate(Vec::new());
+    assert_eq!(child.format_localized(&generated, "short", "fr").unwrap(), "griffes rouge");
+    assert_eq!(child.format_localized(&generated, "[a] [wings]", "fr").unwrap(), "griffes");
+    assert_eq!(child.format_localized(&generated, "[Color] [wings]", "en").unwrap(), "Red claws");
+    assert!(child.format_localized(&generated, "short", "de").is_err());
+}
@@ -1,24 +1,453 @@
-#![feature(rust_2018_preview, uniform_paths)]
-
-extern crate serde_json;
+extern crate clap;
 extern crate morbitgen;
+extern crate rand;
+extern crate rustyline;
+extern crate serde_json;
+
+mod repl;
+
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use morbitgen::{ChaChaRng, Format, MUTATION, Template, TemplateRegistry};
+use rand::Rng;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process;
 
-use morbitgen::Template;
+const DEFAULT_DIR: &str = "assets";
+
+///How much to print on stderr besides errors
+#[derive(Clone, Copy, PartialEq, PartialOrd)]
+enum Verbosity {
+    Quiet,
+    Normal,
+    Verbose,
+}
 
 fn main() {
-    let base_template = Template::new("base", None);
-    let obj_template = Template::new("obj", Some(&base_template));
-    /*    println!(
-        "Base: {}\nOBJ: {}",
-        serde_json::to_string_pretty(&base_template).unwrap(),
-        serde_json::to_string_pretty(&obj_template).unwrap()
-    );*/
-    println!("{:#?}", obj_template.order);
-    let presets = vec![
-        "flavor:normal".parse().unwrap(),
-        "roll head casing color:yes".parse().unwrap(),
-    ];
-    let generated = obj_template.generate(presets);
-    println!("{}", obj_template.format(&generated, "json").unwrap());
-    println!("{}", obj_template.format(&generated, "full").unwrap());
+    let matches = App::new("morbitgen")
+        .about("Generates characters from templates")
+        .setting(AppSettings::ArgsNegateSubcommands)
+        .arg(
+            Arg::with_name("dir")
+                .short("d")
+                .long("dir")
+                .takes_value(true)
+                .global(true)
+                .help("Directory to load templates, mutations and locales from [default: assets]"),
+        )
+        .arg(
+            Arg::with_name("quiet")
+                .short("q")
+                .long("quiet")
+                .global(true)
+                .conflicts_with("verbose")
+                .help("Only print errors"),
+        )
+        .arg(
+            Arg::with_name("verbose")
+                .short("v")
+                .long("verbose")
+                .global(true)
+                .help("Print the seed and what was loaded"),
+        )
+        .arg(
+            Arg::with_name("template")
+                .multiple(true)
+                .help("Templates to generate from, by name or as a path to a template file"),
+        )
+        .arg(
+            Arg::with_name("preset")
+                .short("p")
+                .long("preset")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .help("Requirement to generate with, like `color:red` or `mutation:slimy`"),
+        )
+        .arg(
+            Arg::with_name("count")
+                .short("n")
+                .long("count")
+                .takes_value(true)
+                .default_value("1")
+                .help("Characters to generate from each template"),
+        )
+        .arg(
+            Arg::with_name("seed")
+                .short("s")
+                .long("seed")
+                .takes_value(true)
+                .global(true)
+                .help("Seed for the same characters every time"),
+        )
+        .arg(
+            Arg::with_name("format")
+                .short("f")
+                .long("format")
+                .takes_value(true)
+                .default_value("json")
+                .help("json, csv, tsv, markdown, html or the name of a formatting entry"),
+        )
+        .arg(
+            Arg::with_name("sort order")
+                .long("sort-order")
+                .help("Generate attributes after what they depend on instead of in the declared order"),
+        )
+        .arg(
+            Arg::with_name("locale")
+                .short("l")
+                .long("locale")
+                .takes_value(true)
+                .help("Language to format in"),
+        )
+        .arg(
+            Arg::with_name("output")
+                .short("o")
+                .long("output")
+                .takes_value(true)
+                .help("File to write to instead of stdout"),
+        )
+        .subcommand(
+            SubCommand::with_name("dump")
+                .about("Print a template with its parents merged in, annotated with where attributes came from")
+                .arg(Arg::with_name("template").required(true)),
+        )
+        .subcommand(
+            SubCommand::with_name("convert")
+                .about("Convert a template file to another format")
+                .arg(Arg::with_name("file").required(true))
+                .arg(
+                    Arg::with_name("to")
+                        .long("to")
+                        .takes_value(true)
+                        .required(true)
+                        .possible_values(&["json", "yaml", "toml", "ron"]),
+                ),
+        )
+        .subcommand(SubCommand::with_name("schema").about("Print the JSON Schema for templates"))
+        .subcommand(
+            SubCommand::with_name("lint")
+                .about("Check every template in a directory, exiting with an error if there are problems")
+                .arg(Arg::with_name("lint dir").value_name("DIR").help("Directory to check [default: assets]"))
+                .arg(Arg::with_name("json").long("json").help("Print problems as json")),
+        )
+        .subcommand(
+            SubCommand::with_name("diff")
+                .about("Compare two versions of a template after inheritance")
+                .arg(Arg::with_name("old").required(true).help("Template name or file"))
+                .arg(Arg::with_name("new").required(true).help("Template name or file"))
+                .arg(
+                    Arg::with_name("probabilities")
+                        .long("probabilities")
+                        .help("Also show how much more or less likely each value got"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("graph")
+                .about("Print how a template's attributes depend on each other")
+                .arg(Arg::with_name("template").required(true))
+                .arg(
+                    Arg::with_name("graph format")
+                        .long("format")
+                        .takes_value(true)
+                        .possible_values(&["dot", "mermaid"])
+                        .default_value("dot"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("repl")
+                .about("Roll, reroll and format characters interactively, history is kept in $MORBITGEN_HISTORY or ~/.morbitgen_history")
+                .arg(Arg::with_name("template").required(true)),
+        )
+        .get_matches();
+
+    let verbosity = if matches.is_present("quiet") {
+        Verbosity::Quiet
+    } else if matches.is_present("verbose") {
+        Verbosity::Verbose
+    } else {
+        Verbosity::Normal
+    };
+    let result = match matches.subcommand() {
+        ("dump", Some(sub)) => dump(&matches, sub, verbosity),
+        ("convert", Some(sub)) => convert(sub),
+        ("repl", Some(sub)) => repl(&matches, sub, verbosity),
+        ("lint", Some(sub)) => lint(&matches, sub, verbosity),
+        ("diff", Some(sub)) => diff(&matches, sub, verbosity),
+        ("graph", Some(sub)) => graph(&matches, sub, verbosity),
+        ("schema", _) => {
+            println!("{:#}", morbitgen::schema());
+            Ok(())
+        }
+        _ => generate(&matches, verbosity),
+    };
+    if let Err(error) = result {
+        eprintln!("error: {}", error);
+        process::exit(1);
+    }
+}
+
+///Load the template directory, a missing default directory is fine if every template is given as a file
+fn load_registry(matches: &ArgMatches, verbosity: Verbosity) -> Result<TemplateRegistry, String> {
+    let dir = matches.value_of("dir");
+    let path = dir.unwrap_or(DEFAULT_DIR);
+    if dir.is_none() && !Path::new(path).is_dir() {
+        return Ok(TemplateRegistry::new());
+    }
+    let registry = TemplateRegistry::load_dir(path)?;
+    if verbosity >= Verbosity::Verbose {
+        eprintln!("loaded {} templates from {}", registry.names().len(), path);
+    }
+    Ok(registry)
+}
+
+///Add the template to the registry if it's a path to a template file, returning its name
+fn add_template(registry: &mut TemplateRegistry, template: &str) -> Result<(String, bool), String> {
+    let path = Path::new(template);
+    if Format::from_path(path).is_some() && path.is_file() {
+        let name = path.file_stem().and_then(|name| name.to_str()).unwrap_or(template).to_string();
+        registry.insert(&name, Template::load(path)?);
+        Ok((name, true))
+    } else {
+        Ok((template.to_string(), false))
+    }
+}
+
+///Template names, adding any that are given as files to the registry
+fn templates(matches: &ArgMatches, registry: &mut TemplateRegistry) -> Result<Vec<String>, String> {
+    let mut names = Vec::new();
+    let mut added = false;
+    for template in matches.values_of("template").into_iter().flatten() {
+        let (name, file) = add_template(registry, template)?;
+        names.push(name);
+        added |= file;
+    }
+    if added {
+        registry.resolve()?;
+    }
+    if names.is_empty() {
+        let available: Vec<&str> = registry.names().into_iter().map(|name| name.as_str()).collect();
+        return Err(format!("no template given, available templates are: {}", available.join(", ")));
+    }
+    for name in &names {
+        if registry.get(name).is_none() {
+            return Err(format!("unknown template {}", name));
+        }
+    }
+    Ok(names)
+}
+
+///Seeded from `--seed` or a random seed, which is printed when verbose so it can be reused
+fn random(matches: &ArgMatches, verbosity: Verbosity) -> Result<ChaChaRng, String> {
+    let seed: u64 = match matches.value_of("seed") {
+        Some(seed) => seed.parse().map_err(|_| "seed has to be a whole number".to_string())?,
+        None => rand::thread_rng().gen(),
+    };
+    if verbosity >= Verbosity::Verbose {
+        eprintln!("seed {}", seed);
+    }
+    Ok(morbitgen::seeded(seed))
+}
+
+fn generate(matches: &ArgMatches, verbosity: Verbosity) -> Result<(), String> {
+    let mut registry = load_registry(matches, verbosity)?;
+    let names = templates(matches, &mut registry)?;
+    let count: usize = matches
+        .value_of("count")
+        .unwrap()
+        .parse()
+        .map_err(|_| "count has to be a whole number".to_string())?;
+    let mut random = random(matches, verbosity)?;
+    let presets: Vec<_> = matches
+        .values_of("preset")
+        .into_iter()
+        .flatten()
+        .map(|preset| preset.parse().unwrap())
+        .collect();
+    let format = matches.value_of("format").unwrap();
+    let locale = matches.value_of("locale");
+
+    let delimiter = match format {
+        "csv" => Some(','),
+        "tsv" => Some('\t'),
+        _ => None,
+    };
+    let mut output = Vec::new();
+    let mut json = Vec::new();
+    //one header for every template, with what mutations add
+    let mut columns: Vec<String> = Vec::new();
+    let mut rows = Vec::new();
+    for name in &names {
+        let mut template = registry.get(name).unwrap().clone();
+        let locale = match locale {
+            Some(language) => template.locale(language).map_err(|e| format!("{}: {}", name, e))?,
+            None => template.labels(),
+        };
+        check_format(name, &template, format, &locale.formatting)?;
+        if verbosity >= Verbosity::Normal {
+            for undefined in registry.undefined(name) {
+                eprintln!("warning: {}: {} is in the order but isn't an attribute", name, undefined);
+            }
+        }
+        if matches.is_present("sort order") {
+            template.sort_order();
+        } else if verbosity >= Verbosity::Normal {
+            for dependency in template.order_violations() {
+                eprintln!(
+                    "warning: {}: {} depends on {} which is generated after it, --sort-order or \"auto_order\": true generates it first",
+                    name,
+                    dependency.to,
+                    dependency.from
+                );
+            }
+        }
+        for i in 1..=count {
+            let (mutated, remaining, applied) = registry.mutations().apply(&template, presets.clone(), &mut random)?;
+            let (generated, trace) = mutated.generate_traced_with_rng(remaining, &mut random);
+            if verbosity >= Verbosity::Normal {
+                let unmet = presets
+                    .iter()
+                    .filter(|preset| preset.possibilities.iter().all(|(key, _, _)| key != MUTATION))
+                    .filter(|preset| !preset.met_by(&generated, &trace));
+                for preset in unmet {
+                    eprintln!("warning: {} {}: couldn't meet preset {}", name, i, preset);
+                }
+            }
+            if verbosity >= Verbosity::Verbose && !applied.is_empty() {
+                eprintln!("{} {}: mutations {}", name, i, applied.join(", "));
+            }
+            match format {
+                "json" => json.push(generated.into_iter().collect::<BTreeMap<_, _>>()),
+                "csv" | "tsv" => {
+                    for column in mutated.columns() {
+                        if !columns.contains(&column) {
+                            columns.push(column);
+                        }
+                    }
+                    rows.push(generated);
+                }
+                _ => {
+                    let formatted = mutated.format_with(&generated, format, &locale);
+                    writeln!(output, "{}", formatted.map_err(|e| format!("{}: {}", name, e))?).unwrap();
+                }
+            }
+        }
+    }
+    if let Some(delimiter) = delimiter {
+        morbitgen::write_delimited(&columns, &rows, delimiter, &mut output).map_err(|e| e.to_string())?;
+    }
+    if format == "json" {
+        //a single character on its own, otherwise a list
+        let json = if json.len() == 1 {
+            serde_json::to_string_pretty(&json[0])
+        } else {
+            serde_json::to_string_pretty(&json)
+        };
+        writeln!(output, "{}", json.map_err(|e| e.to_string())?).unwrap();
+    }
+
+    match matches.value_of("output") {
+        Some(path) => {
+            File::create(path)
+                .and_then(|mut file| file.write_all(&output))
+                .map_err(|e| format!("{}: {}", path, e))?;
+            if verbosity >= Verbosity::Normal {
+                eprintln!("wrote {} characters to {}", count * names.len(), path);
+            }
+        }
+        None => io::stdout().write_all(&output).map_err(|e| e.to_string())?,
+    }
+    Ok(())
+}
+
+///Only the built in formats and formatting entries, so a typo doesn't get printed as a formatting string
+fn check_format(name: &str, template: &Template, format: &str, localized: &BTreeMap<String, String>) -> Result<(), String> {
+    const BUILT_IN: &[&str] = &["json", "csv", "tsv", "markdown", "html"];
+    if BUILT_IN.contains(&format) || template.formatting.contains_key(format) || localized.contains_key(format) {
+        return Ok(());
+    }
+    let entries: BTreeSet<&str> = template.formatting.keys().chain(localized.keys()).map(|entry| entry.as_str()).collect();
+    let entries: Vec<&str> = entries.into_iter().collect();
+    Err(format!(
+        "{}: unknown format {}, use {} or a formatting entry{}",
+        name,
+        format,
+        BUILT_IN.join(", "),
+        if entries.is_empty() { String::new() } else { format!(" ({})", entries.join(", ")) }
+    ))
+}
+
+fn dump(matches: &ArgMatches, sub: &ArgMatches, verbosity: Verbosity) -> Result<(), String> {
+    let mut registry = load_registry(matches, verbosity)?;
+    let names = templates(sub, &mut registry)?;
+    println!("{}", registry.get(&names[0]).unwrap().dump(true));
+    Ok(())
+}
+
+fn convert(sub: &ArgMatches) -> Result<(), String> {
+    use std::fs;
+    let path = sub.value_of("file").unwrap();
+    let from = Format::from_path(path).ok_or_else(|| format!("{}: unknown template format", path))?;
+    let to = Format::from_extension(sub.value_of("to").unwrap()).unwrap();
+    let contents = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    println!("{}", morbitgen::convert(&contents, from, to).map_err(|e| format!("{}: {}", path, e))?);
+    Ok(())
+}
+
+fn repl(matches: &ArgMatches, sub: &ArgMatches, verbosity: Verbosity) -> Result<(), String> {
+    let mut registry = load_registry(matches, verbosity)?;
+    let names = templates(sub, &mut registry)?;
+    let random = random(matches, verbosity)?;
+    repl::run(registry, names[0].clone(), random)
+}
+
+fn lint(matches: &ArgMatches, sub: &ArgMatches, verbosity: Verbosity) -> Result<(), String> {
+    let dir = sub.value_of("lint dir").or_else(|| matches.value_of("dir")).unwrap_or(DEFAULT_DIR);
+    let problems = morbitgen::lint_dir(dir)?;
+    if sub.is_present("json") {
+        println!("{}", serde_json::to_string_pretty(&problems).map_err(|e| e.to_string())?);
+    } else {
+        for problem in &problems {
+            println!("{}", problem);
+        }
+    }
+    if problems.is_empty() {
+        if verbosity >= Verbosity::Normal && !sub.is_present("json") {
+            eprintln!("no problems in {}", dir);
+        }
+        Ok(())
+    } else {
+        Err(format!("{} problems in {}", problems.len(), dir))
+    }
+}
+
+fn diff(matches: &ArgMatches, sub: &ArgMatches, verbosity: Verbosity) -> Result<(), String> {
+    //each side gets its own registry since both files are likely to have the same name
+    let resolve = |template: &str| -> Result<Template, String> {
+        let mut registry = load_registry(matches, verbosity)?;
+        let (name, file) = add_template(&mut registry, template)?;
+        if file {
+            registry.resolve()?;
+        }
+        registry.get(&name).cloned().ok_or_else(|| format!("unknown template {}", name))
+    };
+    let old = resolve(sub.value_of("old").unwrap())?;
+    let new = resolve(sub.value_of("new").unwrap())?;
+    for change in morbitgen::diff(&old, &new, sub.is_present("probabilities")) {
+        println!("{}", change);
+    }
+    Ok(())
+}
+
+fn graph(matches: &ArgMatches, sub: &ArgMatches, verbosity: Verbosity) -> Result<(), String> {
+    let mut registry = load_registry(matches, verbosity)?;
+    let names = templates(sub, &mut registry)?;
+    let template = registry.get(&names[0]).unwrap();
+    match sub.value_of("graph format").unwrap() {
+        "mermaid" => println!("{}", template.mermaid()),
+        _ => println!("{}", template.dot()),
+    }
+    Ok(())
 }
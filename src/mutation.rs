@@ -0,0 +1,154 @@
+use super::{Chance, Generated, Requirement, Template, Trace};
+use rand::Rng;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+///Preset key used to force mutations on or off
+pub const MUTATION: &str = "mutation";
+
+///An optional patch applied on top of a template at generation time
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Mutation {
+    ///chance of being rolled, if missing it is only applied when enabled by a preset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    chance: Option<Chance>,
+    ///merged over the template the same way a child template is
+    #[serde(flatten)]
+    patch: Template,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize, Clone)]
+#[serde(transparent)]
+pub struct Mutations {
+    mutations: BTreeMap<String, Mutation>,
+}
+
+impl FromStr for Mutations {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ::serde_json::from_str(s).map_err(|e| e.to_string())
+    }
+}
+
+impl Mutation {
+    fn rolled<R: Rng>(&self, random: &mut R) -> bool {
+        match self.chance {
+            Some(Chance::Always) => true,
+            Some(chance) => random.gen_range(0, 100) < chance.chance(),
+            None => false,
+        }
+    }
+}
+
+impl Mutations {
+    pub fn get(&self, name: &str) -> Option<&Mutation> {
+        self.mutations.get(name)
+    }
+
+    pub fn names(&self) -> Vec<&String> {
+        self.mutations.keys().collect()
+    }
+
+    ///Pick mutations from `mutation:name` and `!mutation:name` presets and their chances, then
+    ///apply them to the template. The remaining presets are returned with the names of what was applied,
+    ///naming a mutation that doesn't exist is an error
+    pub fn apply<R: Rng>(
+        &self,
+        template: &Template,
+        presets: Vec<Requirement>,
+        random: &mut R,
+    ) -> Result<(Template, Vec<Requirement>, Vec<String>), String> {
+        let mut enabled = Vec::new();
+        let mut disabled = Vec::new();
+        let mut remaining = Vec::new();
+        for preset in presets {
+            if preset.possibilities.len() == 1 && preset.possibilities[0].0 == MUTATION {
+                let (_, ref name, not) = preset.possibilities[0];
+                if not {
+                    disabled.push(name.clone());
+                } else {
+                    enabled.push(name.clone());
+                }
+            } else {
+                remaining.push(preset);
+            }
+        }
+        if let Some(name) = enabled.iter().chain(&disabled).find(|name| !self.mutations.contains_key(*name)) {
+            return Err(format!("mutation {} doesn't exist", name));
+        }
+
+        let mut template = template.clone();
+        let mut applied = Vec::new();
+        for (name, mutation) in &self.mutations {
+            if disabled.contains(name) || !(enabled.contains(name) || mutation.rolled(random)) {
+                continue;
+            }
+            let mut patched = mutation.patch.clone();
+            patched.inherit(&template);
+            template = patched;
+            applied.push(name.clone());
+        }
+        Ok((template, remaining, applied))
+    }
+}
+
+impl Template {
+    ///Generate with mutations applied on top of this template, also returning the names of the ones used
+    pub fn generate_mutated<I>(&self, presets: I, mutations: &Mutations) -> Result<(Generated, Vec<String>), String>
+    where
+        I: IntoIterator<Item = Requirement>,
+    {
+        self.generate_mutated_with_rng(presets, mutations, &mut ::rand::thread_rng())
+    }
+
+    pub fn generate_mutated_with_rng<I, R>(
+        &self,
+        presets: I,
+        mutations: &Mutations,
+        random: &mut R,
+    ) -> Result<(Generated, Vec<String>), String>
+    where
+        I: IntoIterator<Item = Requirement>,
+        R: Rng,
+    {
+        let (generated, _, applied) = self.generate_mutated_traced_with_rng(presets, mutations, random)?;
+        Ok((generated, applied))
+    }
+
+    pub fn generate_mutated_traced_with_rng<I, R>(
+        &self,
+        presets: I,
+        mutations: &Mutations,
+        random: &mut R,
+    ) -> Result<(Generated, Trace, Vec<String>), String>
+    where
+        I: IntoIterator<Item = Requirement>,
+        R: Rng,
+    {
+        let (template, presets, applied) = mutations.apply(self, presets.into_iter().collect(), random)?;
+        let (generated, trace) = template.generate_traced_with_rng(presets, random);
+        Ok((generated, trace, applied))
+    }
+}
+
+#[test]
+fn test_mutations() {
+    let template = Template::new_from_string(r#"{"order": ["skin"], "attributes": {
+        "skin": {"choose": {"bare": {}}}
+    }}"#, None);
+    let mutations: Mutations = r#"{
+        "scaly": {"attributes": {"skin": {"choose": {"scales": {"chance": "Always"}}}}},
+        "glowing": {"chance": "Always", "order": ["glow"], "attributes": {"glow": {"choose": {"faint": {}}}}}
+    }"#.parse().unwrap();
+    let (generated, applied) = template.generate_mutated(vec!["mutation:scaly".parse().unwrap()], &mutations).unwrap();
+    assert_eq!(generated["skin"], "scales");
+    assert_eq!(generated["glow"], "faint");
+    assert_eq!(applied, vec!["glowing", "scaly"]);
+
+    let (generated, applied) = template.generate_mutated(vec!["!mutation:glowing".parse().unwrap()], &mutations).unwrap();
+    assert_eq!(generated["skin"], "bare");
+    assert!(applied.is_empty());
+
+    let error = template.generate_mutated(vec!["mutation:wings".parse().unwrap()], &mutations).unwrap_err();
+    assert_eq!(error, "mutation wings doesn't exist");
+}
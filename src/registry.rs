@@ -0,0 +1,327 @@
+use super::{Format, Template, Mutations};
+#[cfg(test)]
+use super::{Chance, Generator};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+///Not a template, loaded into `mutations` instead
+const MUTATIONS_FILE: &str = "mutations";
+
+///Templates by name, with inheritance from `extends` and `mixins` resolved
+///
+///Precedence from lowest to highest is parents in the order they are listed, then mixins in the order
+///they are listed, then the template itself
+#[derive(Debug, Default)]
+pub struct TemplateRegistry {
+    ///templates as they were loaded
+    sources: BTreeMap<String, Template>,
+    ///templates with all their parents merged in
+    templates: BTreeMap<String, Template>,
+    ///overlays that can be applied to any template
+    mutations: Mutations,
+}
+
+impl TemplateRegistry {
+    pub fn new() -> TemplateRegistry {
+        Default::default()
+    }
+
+    ///Load every template in a directory and resolve them, `mutations` is loaded as mutations and
+    ///`locales/<template>.<language>` as locales for templates. Files can be json, yaml, toml or ron
+    pub fn load_dir<P: AsRef<Path>>(path: P) -> Result<TemplateRegistry, String> {
+        use std::fs;
+        let path = path.as_ref();
+        let mut registry = TemplateRegistry::new();
+        let entries = fs::read_dir(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        for entry in entries {
+            let file = entry.map_err(|e| e.to_string())?.path();
+            let format = match Format::from_path(&file) {
+                Some(format) => format,
+                None => continue,
+            };
+            let name = match file.file_stem().and_then(|name| name.to_str()) {
+                Some(name) => name.to_string(),
+                None => continue,
+            };
+            if registry.sources.contains_key(&name) {
+                return Err(format!("{}: there is already a template called {}", file.display(), name));
+            }
+            let contents = fs::read_to_string(&file).map_err(|e| format!("{}: {}", file.display(), e))?;
+            if name == MUTATIONS_FILE {
+                registry.mutations = format.parse(&contents).map_err(|e| format!("{}: {}", file.display(), e))?;
+            } else {
+                let template = format.parse(&contents).map_err(|e| format!("{}: {}", file.display(), e))?;
+                registry.insert(&name, template);
+            }
+        }
+        registry.load_locales(&path.join("locales"))?;
+        registry.resolve()?;
+        Ok(registry)
+    }
+
+    fn load_locales(&mut self, path: &Path) -> Result<(), String> {
+        use std::fs;
+        if !path.is_dir() {
+            return Ok(());
+        }
+        let entries = fs::read_dir(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        for entry in entries {
+            let file = entry.map_err(|e| e.to_string())?.path();
+            let format = match Format::from_path(&file) {
+                Some(format) => format,
+                None => continue,
+            };
+            let stem = file.file_stem().and_then(|name| name.to_str()).unwrap_or("");
+            let (name, language) = match stem.find('.') {
+                Some(i) => (&stem[..i], &stem[i + 1..]),
+                None => return Err(format!("{}: locale files are named <template>.<language>.<format>", file.display())),
+            };
+            let contents = fs::read_to_string(&file).map_err(|e| format!("{}: {}", file.display(), e))?;
+            let locale = format.parse(&contents).map_err(|e| format!("{}: {}", file.display(), e))?;
+            match self.sources.get_mut(name) {
+                Some(template) => {
+                    template.locales.insert(language.to_string(), locale);
+                }
+                None => return Err(format!("{}: unknown template {}", file.display(), name)),
+            }
+        }
+        Ok(())
+    }
+
+    ///Add an unresolved template, `resolve` has to be called before it can be used
+    pub fn add(&mut self, name: &str, string: &str) -> Result<(), String> {
+        let template = string.parse()?;
+        self.insert(name, template);
+        Ok(())
+    }
+
+    pub fn insert(&mut self, name: &str, template: Template) {
+        self.sources.insert(name.to_string(), template);
+    }
+
+    ///Add or replace a template and resolve everything again, leaving the registry as it was if that fails
+    pub fn register(&mut self, name: &str, template: Template) -> Result<(), String> {
+        let previous = self.sources.insert(name.to_string(), template);
+        if let Err(e) = self.resolve() {
+            match previous {
+                Some(previous) => self.sources.insert(name.to_string(), previous),
+                None => self.sources.remove(name),
+            };
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    ///Remove a template, as long as nothing else inherits from it
+    pub fn remove(&mut self, name: &str) -> Result<Template, String> {
+        if !self.sources.contains_key(name) {
+            return Err(format!("unknown template {}", name));
+        }
+        let children: Vec<&str> = self
+            .sources
+            .iter()
+            .filter(|&(child, source)| child != name && source.extends.iter().chain(&source.mixins).any(|parent| parent == name))
+            .map(|(child, _)| child.as_str())
+            .collect();
+        if !children.is_empty() {
+            return Err(format!("{} inherits from {}", children.join(", "), name));
+        }
+        self.templates.remove(name);
+        Ok(self.sources.remove(name).unwrap())
+    }
+
+    ///Resolve inheritance for every template, parents first
+    pub fn resolve(&mut self) -> Result<(), String> {
+        let mut resolved = BTreeMap::new();
+        for name in self.sources.keys() {
+            self.resolve_template(name, &mut Vec::new(), &mut resolved)?;
+        }
+        self.templates = resolved;
+        Ok(())
+    }
+
+    fn resolve_template(
+        &self,
+        name: &str,
+        stack: &mut Vec<String>,
+        resolved: &mut BTreeMap<String, Template>,
+    ) -> Result<(), String> {
+        if resolved.contains_key(name) {
+            return Ok(());
+        }
+        if stack.iter().any(|parent| parent == name) {
+            stack.push(name.to_string());
+            return Err(format!("inheritance cycle: {}", stack.join(" -> ")));
+        }
+        let source = match self.sources.get(name) {
+            Some(source) => source,
+            None => {
+                return Err(match stack.last() {
+                    Some(child) => format!("{} inherits from unknown template {}", child, name),
+                    None => format!("unknown template {}", name),
+                })
+            }
+        };
+        stack.push(name.to_string());
+        for parent in source.extends.iter().chain(&source.mixins) {
+            self.resolve_template(parent, stack, resolved)?;
+        }
+        stack.pop();
+
+        resolved.insert(name.to_string(), merge_parents(name, source, resolved));
+        Ok(())
+    }
+
+    ///Resolve a template against the templates already in the registry without adding it
+    pub fn resolve_with(&self, name: &str, template: &Template) -> Result<Template, String> {
+        for parent in template.extends.iter().chain(&template.mixins) {
+            if !self.templates.contains_key(parent) {
+                return Err(format!("{} inherits from unknown template {}", name, parent));
+            }
+        }
+        Ok(merge_parents(name, template, &self.templates))
+    }
+
+    ///Get a resolved template
+    pub fn get(&self, name: &str) -> Option<&Template> {
+        self.templates.get(name)
+    }
+
+    ///Get a template as it was loaded, before inheritance
+    pub fn source(&self, name: &str) -> Option<&Template> {
+        self.sources.get(name)
+    }
+
+    ///Whether `child` extends or mixes in `parent`, directly or through other templates
+    pub fn inherits(&self, child: &str, parent: &str) -> bool {
+        self.sources.get(child).is_some_and(|source| {
+            source.extends.iter().chain(&source.mixins).any(|name| name == parent || self.inherits(name, parent))
+        })
+    }
+
+    ///Whether a template inheriting from `name` has the attribute
+    pub fn defined_below(&self, name: &str, attribute: &str) -> bool {
+        self.templates.iter().filter(|&(child, _)| self.inherits(child, name)).any(|(_, child)| {
+            child.attributes.contains_key(child.rename.get(attribute).map_or(attribute, |name| name.as_str()))
+        })
+    }
+
+    ///`Template::undefined` for a resolved template, leaving out entries that templates inheriting from it
+    ///define, since those are what the entries are for
+    pub fn undefined(&self, name: &str) -> Vec<&String> {
+        match self.templates.get(name) {
+            Some(template) => template.undefined().into_iter().filter(|attribute| !self.defined_below(name, attribute)).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    pub fn names(&self) -> Vec<&String> {
+        self.templates.keys().collect()
+    }
+
+    pub fn mutations(&self) -> &Mutations {
+        &self.mutations
+    }
+
+    pub fn set_mutations(&mut self, mutations: Mutations) {
+        self.mutations = mutations;
+    }
+}
+
+///Merge resolved parents into a copy of the template
+fn merge_parents(name: &str, source: &Template, resolved: &BTreeMap<String, Template>) -> Template {
+    let mut template = source.clone();
+    template.set_origin(name);
+    //whatever is merged first takes precedence, so go from highest to lowest
+    for parent in source.mixins.iter().rev().chain(source.extends.iter().rev()) {
+        template.inherit(&resolved[parent]);
+    }
+    template.apply_removals();
+    if template.auto_order {
+        template.sort_order();
+    }
+    template.extends.clear();
+    template.mixins.clear();
+    template
+}
+
+#[test]
+fn test_registry() {
+    let mut registry = TemplateRegistry::new();
+    registry.add("a", r#"{"order": ["x"], "attributes": {"x": {"choose": {"one": {}}}}}"#).unwrap();
+    registry.add("b", r#"{"extends": "a", "order": ["y"], "attributes": {}}"#).unwrap();
+    registry.add("c", r#"{"extends": ["b"], "order": ["z"], "attributes": {}}"#).unwrap();
+    registry.resolve().unwrap();
+    assert_eq!(registry.get("c").unwrap().order, vec!["x", "y", "z"]);
+    assert!(registry.get("c").unwrap().attributes.contains_key("x"));
+    let dump = registry.get("c").unwrap().dump(true);
+    assert!(dump.contains(r#""from": "a""#));
+    let reloaded: Template = dump.parse().unwrap();
+    assert_eq!(reloaded.dump(true), dump);
+
+    registry.add("b", r#"{"extends": "a", "order": ["y", "w"], "attributes": {}}"#).unwrap();
+    registry.add("c", r#"{"extends": ["b"], "order": ["z"], "attributes": {"w": {"choose": ["one"]}}}"#).unwrap();
+    registry.resolve().unwrap();
+    assert!(registry.inherits("c", "a") && !registry.inherits("a", "c"));
+    assert_eq!(registry.get("b").unwrap().undefined(), vec!["y", "w"]);
+    assert_eq!(registry.undefined("b"), vec!["y"]);
+
+    registry.add("a", r#"{"extends": "c", "order": [], "attributes": {}}"#).unwrap();
+    assert!(registry.resolve().unwrap_err().contains("cycle"));
+}
+
+#[test]
+fn test_mixins() {
+    let mut registry = TemplateRegistry::new();
+    registry.add("base", r#"{"order": ["skin"], "attributes": {"skin": {"choose": {"bare": {}}}}}"#).unwrap();
+    registry.add("aquatic", r#"{"order": ["gills"], "attributes": {
+        "skin": {"choose": {"scales": {"chance": "Rare"}}},
+        "gills": {"choose": {"yes": {}}}
+    }}"#).unwrap();
+    registry.add("mechanical", r#"{"attributes": {"skin": {"choose": {"scales": {"chance": "Common"}, "plating": {}}}}}"#).unwrap();
+    registry.add("species", r#"{"extends": "base", "mixins": ["aquatic", "mechanical"], "order": [], "attributes": {}}"#).unwrap();
+    registry.resolve().unwrap();
+    let species = registry.get("species").unwrap();
+    assert_eq!(species.order, vec!["skin", "gills"]);
+    match species.attributes["skin"].generator {
+        Generator::Choose(ref choices) => {
+            assert_eq!(choices.keys().collect::<Vec<_>>(), vec!["bare", "plating", "scales"]);
+            assert_eq!(choices["scales"].chance, Some(Chance::Common));
+        }
+        _ => panic!("skin should still choose"),
+    }
+}
+
+#[test]
+fn test_register() {
+    let mut registry = TemplateRegistry::new();
+    registry.register("a", r#"{"order": ["x"], "attributes": {"x": {"choose": ["one"]}}}"#.parse().unwrap()).unwrap();
+    registry.register("b", r#"{"extends": "a", "order": ["y"], "attributes": {}}"#.parse().unwrap()).unwrap();
+    assert_eq!(registry.get("b").unwrap().order, vec!["x", "y"]);
+    let error = registry.register("a", r#"{"extends": "b", "order": [], "attributes": {}}"#.parse().unwrap());
+    assert!(error.unwrap_err().contains("cycle"));
+    assert_eq!(registry.source("a").unwrap().order, vec!["x"]);
+    assert_eq!(registry.remove("a").unwrap_err(), "b inherits from a");
+    registry.remove("b").unwrap();
+    registry.remove("a").unwrap();
+    assert!(registry.names().is_empty());
+}
+
+#[test]
+fn test_diamond() {
+    let mut registry = TemplateRegistry::new();
+    registry.add("base", r#"{"order": ["color", "tint"], "attributes": {
+        "color": {"choose": ["blue"]},
+        "tint": {"reuse": "color"}
+    }}"#).unwrap();
+    registry.add("a", r#"{"extends": "base", "attributes": {"color": {"choose": ["red"]}}}"#).unwrap();
+    registry.add("b", r#"{"extends": "base", "attributes": {}}"#).unwrap();
+    registry.add("c", r#"{"extends": ["a", "b"], "attributes": {}}"#).unwrap();
+    registry.resolve().unwrap();
+    let c = registry.get("c").unwrap();
+    assert_eq!(c.attributes["tint"].generator, Generator::Reuse("color".to_string()));
+    match c.attributes["color"].generator {
+        Generator::Choose(ref choices) => assert_eq!(choices.keys().collect::<Vec<_>>(), vec!["blue", "red"]),
+        _ => panic!("color should still choose"),
+    }
+}
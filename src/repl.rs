@@ -0,0 +1,292 @@
+use morbitgen::{ChaChaRng, Locale, Requirement, Template, TemplateRegistry, Trace};
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::path::PathBuf;
+
+const HISTORY_FILE: &str = ".morbitgen_history";
+
+const COMMANDS: &[&str] = &[
+    "attributes", "explain", "format", "help", "presets", "quit", "reroll", "roll", "set", "show", "template",
+    "templates", "unset", "values",
+];
+
+const HELP: &str = "\
+template <name>         switch to another template
+templates               list templates
+attributes              list the template's attributes in order
+values <attribute>      list the values an attribute can have
+set <attribute:value>   add a preset, anything `requires` accepts works
+unset <attribute>       remove presets for an attribute
+presets                 list presets
+roll                    generate a new character, with mutations
+reroll <attribute>      generate one attribute again keeping the rest
+show                    print the character as json and the mutations it has
+explain                 show the options chosen for each attribute
+format <entry|string>   format the character with a formatting entry or string
+quit                    leave";
+
+///Names for tab completion, refreshed whenever the template changes
+#[derive(Default)]
+struct Names {
+    templates: Vec<String>,
+    attributes: Vec<String>,
+    values: BTreeMap<String, Vec<String>>,
+    formatting: Vec<String>,
+}
+
+impl Names {
+    fn new(registry: &TemplateRegistry, template: &Template) -> Names {
+        let attributes = template.columns();
+        Names {
+            templates: registry.names().into_iter().cloned().collect(),
+            values: attributes.iter().map(|name| (name.clone(), template.values(name))).collect(),
+            attributes,
+            formatting: template.formatting.keys().cloned().collect(),
+        }
+    }
+
+    ///Candidates for the argument of a command and where they start in the line
+    fn candidates<'a>(&'a self, command: &str, argument: &'a str, start: usize) -> (usize, Vec<&'a String>) {
+        let matching = |names: &'a Vec<String>, prefix: &str| names.iter().filter(|name| name.starts_with(prefix)).collect();
+        match command {
+            "template" => (start, matching(&self.templates, argument)),
+            "reroll" | "unset" | "values" => (start, matching(&self.attributes, argument)),
+            "set" => match argument.find(':') {
+                Some(i) => match self.values.get(&argument[..i]) {
+                    Some(values) => (start + i + 1, matching(values, &argument[i + 1..])),
+                    None => (start, Vec::new()),
+                },
+                None => (start, matching(&self.attributes, argument)),
+            },
+            "format" => match argument.rfind('[') {
+                Some(i) => (start + i + 1, matching(&self.attributes, &argument[i + 1..])),
+                None => (start, matching(&self.formatting, argument)),
+            },
+            _ => (start, Vec::new()),
+        }
+    }
+}
+
+impl Completer for Names {
+    type Candidate = String;
+
+    fn complete(&self, line: &str, pos: usize, _: &Context) -> ::rustyline::Result<(usize, Vec<String>)> {
+        let line = &line[..pos];
+        match line.find(' ') {
+            Some(i) => {
+                let (start, candidates) = self.candidates(&line[..i], &line[i + 1..], i + 1);
+                Ok((start, candidates.into_iter().cloned().collect()))
+            }
+            None => Ok((0, COMMANDS.iter().filter(|command| command.starts_with(line)).map(|command| command.to_string()).collect())),
+        }
+    }
+}
+
+impl Hinter for Names {
+    type Hint = String;
+}
+
+impl Highlighter for Names {}
+
+impl Validator for Names {}
+
+impl Helper for Names {}
+
+///`MORBITGEN_HISTORY` if it's set, otherwise `.morbitgen_history` in the home directory
+fn history_file() -> Option<PathBuf> {
+    if let Some(path) = env::var_os("MORBITGEN_HISTORY") {
+        return Some(PathBuf::from(path));
+    }
+    env::var_os("HOME").or_else(|| env::var_os("USERPROFILE")).map(|home| PathBuf::from(home).join(HISTORY_FILE))
+}
+
+///Split a line into its command and argument
+fn split(line: &str) -> (&str, &str) {
+    match line.find(' ') {
+        Some(i) => (&line[..i], line[i + 1..].trim()),
+        None => (line, ""),
+    }
+}
+
+struct Session {
+    registry: TemplateRegistry,
+    name: String,
+    ///the template with the mutations of the last roll applied
+    mutated: Template,
+    labels: Locale,
+    presets: Vec<String>,
+    generated: HashMap<String, String>,
+    trace: Trace,
+    mutations: Vec<String>,
+    random: ChaChaRng,
+}
+
+impl Session {
+    fn new(registry: TemplateRegistry, name: String, random: ChaChaRng) -> Result<Session, String> {
+        let mutated = registry.get(&name).ok_or_else(|| format!("unknown template {}", name))?.clone();
+        let labels = mutated.labels();
+        let mut session = Session {
+            registry,
+            name,
+            mutated,
+            labels,
+            presets: Vec::new(),
+            generated: HashMap::new(),
+            trace: Trace::new(),
+            mutations: Vec::new(),
+            random,
+        };
+        session.roll()?;
+        Ok(session)
+    }
+
+    fn template(&self) -> &Template {
+        self.registry.get(&self.name).unwrap()
+    }
+
+    fn presets(&self) -> Vec<Requirement> {
+        self.presets.iter().map(|preset| preset.parse().unwrap()).collect()
+    }
+
+    fn roll(&mut self) -> Result<(), String> {
+        let presets = self.presets();
+        let template = self.registry.get(&self.name).unwrap();
+        let (mutated, presets, mutations) = self.registry.mutations().apply(template, presets, &mut self.random)?;
+        let (generated, trace) = mutated.generate_traced_with_rng(presets, &mut self.random);
+        self.labels = mutated.labels();
+        self.mutated = mutated;
+        self.generated = generated;
+        self.trace = trace;
+        self.mutations = mutations;
+        Ok(())
+    }
+
+    fn show(&self) -> Result<String, String> {
+        let json = self.mutated.format_with(&self.generated, "json", &self.labels)?;
+        if self.mutations.is_empty() {
+            Ok(json)
+        } else {
+            Ok(format!("{}\nmutations: {}", json, self.mutations.join(", ")))
+        }
+    }
+
+    ///Run one line, returning what to print
+    fn run(&mut self, command: &str, argument: &str) -> Result<String, String> {
+        match command {
+            "template" => {
+                if self.registry.get(argument).is_none() {
+                    return Err(format!("unknown template {}", argument));
+                }
+                self.name = argument.to_string();
+                self.roll()?;
+                self.show()
+            }
+            "templates" => Ok(self.registry.names().into_iter().cloned().collect::<Vec<_>>().join("\n")),
+            "attributes" => Ok(self.template().columns().join("\n")),
+            "values" => Ok(self.template().values(argument).join("\n")),
+            "set" => {
+                self.presets.push(argument.to_string());
+                Ok(String::new())
+            }
+            "unset" => {
+                let prefix = format!("{}:", argument);
+                self.presets.retain(|preset| preset != argument && !preset.starts_with(&prefix));
+                Ok(String::new())
+            }
+            "presets" => Ok(self.presets.join("\n")),
+            "roll" => {
+                self.roll()?;
+                self.show()
+            }
+            "reroll" => {
+                let presets = self.presets();
+                self.mutated.reroll(&mut self.generated, &mut self.trace, argument, presets, &mut self.random)?;
+                Ok(format!("{}: {}", argument, self.generated.get(argument).map_or("", |value| value.as_str())))
+            }
+            "show" => self.show(),
+            "explain" => Ok(self.mutated.explain(&self.generated, &self.trace)),
+            "format" => self.mutated.format_with(&self.generated, argument, &self.labels),
+            "help" => Ok(HELP.to_string()),
+            _ => Err(format!("unknown command {}, try help", command)),
+        }
+    }
+}
+
+pub fn run(registry: TemplateRegistry, name: String, random: ChaChaRng) -> Result<(), String> {
+    let mut session = Session::new(registry, name, random)?;
+    let mut editor = Editor::<Names>::new();
+    editor.set_helper(Some(Names::new(&session.registry, session.template())));
+    let history = history_file();
+    if let Some(ref history) = history {
+        let _ = editor.load_history(history);
+    }
+    println!("{}", session.show()?);
+    loop {
+        let line = match editor.readline(&format!("{}> ", session.name)) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) | Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(e.to_string()),
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        editor.add_history_entry(line);
+        let (command, argument) = split(line);
+        if command == "quit" || command == "exit" {
+            break;
+        }
+        match session.run(command, argument) {
+            Ok(ref output) if output.is_empty() => {}
+            Ok(output) => println!("{}", output),
+            Err(e) => eprintln!("error: {}", e),
+        }
+        if command == "template" {
+            editor.set_helper(Some(Names::new(&session.registry, session.template())));
+        }
+    }
+    match history {
+        Some(history) => editor.save_history(&history).map_err(|e| format!("{}: {}", history.display(), e)),
+        None => Ok(()),
+    }
+}
+
+#[test]
+fn test_session() {
+    let mut registry = TemplateRegistry::new();
+    let template = r#"{"order": ["color", "size"], "attributes": {
+        "color": {"choose": {"red": {"label": "Red"}, "blue": {}}},
+        "size": {"choose": ["small"]}
+    }}"#;
+    registry.insert("obj", template.parse().unwrap());
+    registry.resolve().unwrap();
+    registry.set_mutations(r#"{"huge": {"chance": "Always", "order": ["weight"], "attributes": {
+        "size": {"choose": {"huge": "Always"}},
+        "weight": {"choose": ["heavy"]}
+    }}}"#.parse().unwrap());
+    let mut session = Session::new(registry, "obj".to_string(), morbitgen::seeded(3)).unwrap();
+    let script = "set color:red\npresets\nroll\nformat [color] [size]\nexplain\nreroll color\nreroll size\nreroll weight\n\
+                  unset color\npresets\nvalues color\ntemplate nothing\nfly";
+    let output: Vec<_> = script.lines().map(|line| {
+        let (command, argument) = split(line);
+        session.run(command, argument)
+    }).collect();
+    assert_eq!(output[0], Ok(String::new()));
+    assert_eq!(output[1], Ok("color:red".to_string()));
+    assert_eq!(output[2], Ok("{\n  \"color\": \"red\",\n  \"size\": \"huge\",\n  \"weight\": \"heavy\"\n}\nmutations: huge".to_string()));
+    assert_eq!(output[3], Ok("Red huge".to_string()));
+    assert_eq!(output[4], Ok("color: red (preset)\nsize: huge\nweight: heavy".to_string()));
+    assert_eq!(output[5], Ok("color: red".to_string()));
+    assert_eq!(output[6], Ok("size: huge".to_string()));
+    assert_eq!(output[7], Ok("weight: heavy".to_string()));
+    assert_eq!(output[9], Ok(String::new()));
+    assert_eq!(output[10], Ok("blue\nred".to_string()));
+    assert_eq!(output[11], Err("unknown template nothing".to_string()));
+    assert_eq!(output[12], Err("unknown command fly, try help".to_string()));
+}
@@ -0,0 +1,154 @@
+use serde_json::Value;
+
+///Names a chance can be written as, from least to most likely
+const CHANCES: &[&str] = &[
+    "Never",
+    "ExtremelyRare",
+    "VeryRare",
+    "Rare",
+    "Uncommon",
+    "Standard",
+    "Common",
+    "VeryCommon",
+    "ExtremelyCommon",
+    "Always",
+];
+
+///One or more `attribute:value` possibilities separated by `|`, each optionally negated with `!`
+const REQUIREMENT: &str = r"^!?[^:|]+(:[^:|]*)?(\|!?[^:|]+(:[^:|]*)?)*$";
+
+///JSON Schema describing template files, for editors to validate against
+pub fn schema() -> Value {
+    json!({
+        "$schema": "http://json-schema.org/draft-07/schema#",
+        "title": "Template",
+        "type": "object",
+        "properties": {
+            "extends": {"$ref": "#/definitions/names", "description": "templates to inherit from, later ones take precedence"},
+            "mixins": {"$ref": "#/definitions/names", "description": "partial templates to mix in"},
+            "order": {"type": "array", "items": {"type": "string"}, "description": "order attributes are generated in"},
+            "auto_order": {"type": "boolean", "description": "generate attributes after what they depend on instead of in the declared order"},
+            "remove": {"type": "array", "items": {"type": "string"}, "description": "inherited attributes to drop completely"},
+            "disable": {"type": "array", "items": {"type": "string"}, "description": "inherited attributes to keep but not generate"},
+            "attributes": {"type": "object", "additionalProperties": {"$ref": "#/definitions/attribute"}},
+            "rename": {"type": "object", "additionalProperties": {"type": "string"}},
+            "formatting": {"$ref": "#/definitions/formattings"},
+            "locales": {"type": "object", "additionalProperties": {"$ref": "#/definitions/locale"}},
+            "sections": {"type": "array", "items": {"$ref": "#/definitions/section"}}
+        },
+        "additionalProperties": false,
+        "definitions": {
+            "names": {
+                "oneOf": [
+                    {"type": "string"},
+                    {"type": "array", "items": {"type": "string"}}
+                ]
+            },
+            "chance": {"enum": CHANCES},
+            "requirement": {
+                "type": "string",
+                "pattern": REQUIREMENT,
+                "description": "attribute:value possibilities separated by |, ! to deny"
+            },
+            "attribute": {
+                "oneOf": [
+                    {"$ref": "#/definitions/chance"},
+                    {"type": "null"},
+                    {
+                        "type": "object",
+                        "properties": {
+                            "choose": {
+                                "oneOf": [
+                                    {"type": "array", "items": {"type": "string"}, "uniqueItems": true},
+                                    {"type": "object", "additionalProperties": {"$ref": "#/definitions/attribute"}}
+                                ]
+                            },
+                            "reuse": {"type": "string"},
+                            "copy": {"type": "string"},
+                            "nothing": {"type": "null"},
+                            "replace": {"type": "boolean"},
+                            "chance": {"$ref": "#/definitions/chance"},
+                            "requires": {"type": "array", "items": {"$ref": "#/definitions/requirement"}},
+                            "remove": {"type": "array", "items": {"type": "string"}},
+                            "from": {"type": "string"},
+                            "alias": {"type": "string"},
+                            "label": {"type": "string"},
+                            "description": {"type": "string"}
+                        },
+                        "additionalProperties": false,
+                        "not": {
+                            "anyOf": [
+                                {"required": ["choose", "reuse"]},
+                                {"required": ["choose", "copy"]},
+                                {"required": ["choose", "nothing"]},
+                                {"required": ["reuse", "copy"]},
+                                {"required": ["reuse", "nothing"]},
+                                {"required": ["copy", "nothing"]}
+                            ]
+                        }
+                    }
+                ]
+            },
+            "formattings": {
+                "type": "object",
+                "additionalProperties": {
+                    "type": "string",
+                    "description": "text with [attribute|filter], [+verb], [switch attribute|value:text] and [requirement?then|else] substitutions"
+                }
+            },
+            "locale": {
+                "type": "object",
+                "properties": {
+                    "english": {"type": "boolean"},
+                    "attributes": {"type": "object", "additionalProperties": {"type": "string"}},
+                    "values": {
+                        "type": "object",
+                        "additionalProperties": {"type": "object", "additionalProperties": {"type": "string"}}
+                    },
+                    "attribute_descriptions": {"type": "object", "additionalProperties": {"type": "string"}},
+                    "descriptions": {
+                        "type": "object",
+                        "additionalProperties": {"type": "object", "additionalProperties": {"type": "string"}}
+                    },
+                    "formatting": {"$ref": "#/definitions/formattings"}
+                }
+            },
+            "section": {
+                "type": "object",
+                "properties": {
+                    "name": {"type": "string"},
+                    "attributes": {"type": "array", "items": {"type": "string"}}
+                },
+                "required": ["name", "attributes"]
+            }
+        }
+    })
+}
+
+#[test]
+fn test_schema() {
+    extern crate jsonschema;
+    use super::{Chance, Format, Template};
+    use serde::Deserialize;
+    use serde::de::IntoDeserializer;
+    use serde::de::value::Error;
+
+    for name in CHANCES {
+        assert!(Chance::deserialize(IntoDeserializer::<Error>::into_deserializer(*name)).is_ok(), "{}", name);
+    }
+    let schema = schema();
+    let checked_in: Value = ::serde_json::from_str(include_str!("../template.schema.json")).unwrap();
+    assert_eq!(checked_in, schema, "template.schema.json is out of date");
+    let compiled = jsonschema::JSONSchema::compile(&schema).unwrap();
+    let base = include_str!("../assets/base.json");
+    let obj = include_str!("../assets/obj.json");
+    for source in &[base, obj] {
+        let template: Value = ::serde_json::from_str(source).unwrap();
+        assert!(compiled.is_valid(&template));
+        let converted: Template = Format::Json.parse(source).unwrap();
+        assert!(compiled.is_valid(&::serde_json::to_value(&converted).unwrap()));
+    }
+    let invalid = json!({"attributes": {"size": {"choose": ["small"], "reuse": "height", "chance": "Sometimes"}}});
+    assert!(!compiled.is_valid(&invalid));
+    assert!(!compiled.is_valid(&json!({"atributes": {}})));
+}
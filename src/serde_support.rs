@@ -1,6 +1,23 @@
-use super::{Requirement, Attribute, Generator};
+use super::{Requirement, Attribute, Attributes, Chance, Generator, Template};
 use serde::{Deserialize, Deserializer, de, Serialize, Serializer};
 
+///Accept either a single string or a list of strings
+pub fn one_or_many<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(name) => vec![name],
+        OneOrMany::Many(names) => names,
+    })
+}
+
 impl<'de> Deserialize<'de> for Requirement {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
@@ -28,15 +45,46 @@ impl<'de> Deserialize<'de> for Attribute {
     {
         use std::fmt;
         use serde::de::{Visitor, MapAccess};
-        #[derive(Deserialize)]
-        #[serde(field_identifier, rename_all = "lowercase")]
         enum Field {
             Choose,
             Reuse,
+            Copy,
             Nothing,
             Replace,
             Chance,
             Requires,
+            Remove,
+            From,
+            Alias,
+            Label,
+            Description,
+        }
+
+        const FIELDS: &[&str] =
+            &["choose", "reuse", "copy", "nothing", "replace", "chance", "requires", "remove", "from", "alias", "label", "description"];
+
+        //read as a plain string, ron only takes bare identifiers for struct fields
+        impl<'de> Deserialize<'de> for Field {
+            fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+            where
+                D: Deserializer<'de>,
+            {
+                Ok(match String::deserialize(deserializer)?.as_str() {
+                    "choose" => Field::Choose,
+                    "reuse" => Field::Reuse,
+                    "copy" => Field::Copy,
+                    "nothing" => Field::Nothing,
+                    "replace" => Field::Replace,
+                    "chance" => Field::Chance,
+                    "requires" => Field::Requires,
+                    "remove" => Field::Remove,
+                    "from" => Field::From,
+                    "alias" => Field::Alias,
+                    "label" => Field::Label,
+                    "description" => Field::Description,
+                    other => return Err(de::Error::unknown_field(other, FIELDS)),
+                })
+            }
         }
 
         struct AttributeVisitor;
@@ -44,7 +92,27 @@ impl<'de> Deserialize<'de> for Attribute {
             type Value = Attribute;
 
             fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-                formatter.write_str("struct Attribute")
+                formatter.write_str("struct Attribute or chance")
+            }
+
+            ///`"Rare"` is short for `{"chance": "Rare"}`
+            fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                use serde::de::IntoDeserializer;
+                Ok(Attribute {
+                    chance: Some(Chance::deserialize(value.into_deserializer())?),
+                    ..Default::default()
+                })
+            }
+
+            ///ron reads `()` as unit
+            fn visit_unit<E>(self) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                Ok(Default::default())
             }
 
             fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
@@ -55,6 +123,11 @@ impl<'de> Deserialize<'de> for Attribute {
                 let mut replace = None;
                 let mut chance = None;
                 let mut requires = None;
+                let mut remove = None;
+                let mut origin = None;
+                let mut alias = None;
+                let mut label = None;
+                let mut description = None;
                 while let Some(key) = map.next_key()? {
                     match key {
                         Field::Replace => {
@@ -75,11 +148,41 @@ impl<'de> Deserialize<'de> for Attribute {
                             }
                             requires = Some(map.next_value()?);
                         }
+                        Field::Remove => {
+                            if remove.is_some() {
+                                return Err(de::Error::duplicate_field("remove"));
+                            }
+                            remove = Some(map.next_value()?);
+                        }
+                        Field::From => {
+                            if origin.is_some() {
+                                return Err(de::Error::duplicate_field("from"));
+                            }
+                            origin = Some(map.next_value()?);
+                        }
+                        Field::Alias => {
+                            if alias.is_some() {
+                                return Err(de::Error::duplicate_field("alias"));
+                            }
+                            alias = Some(map.next_value()?);
+                        }
+                        Field::Label => {
+                            if label.is_some() {
+                                return Err(de::Error::duplicate_field("label"));
+                            }
+                            label = Some(map.next_value()?);
+                        }
+                        Field::Description => {
+                            if description.is_some() {
+                                return Err(de::Error::duplicate_field("description"));
+                            }
+                            description = Some(map.next_value()?);
+                        }
                         Field::Choose => {
                             if generator.is_some() {
                                 return Err(de::Error::duplicate_field("generator"));
                             }
-                            generator = Some(Generator::Choose(map.next_value()?));
+                            generator = Some(Generator::Choose(map.next_value::<Choices>()?.0));
                         }
                         Field::Reuse => {
                             if generator.is_some() {
@@ -87,6 +190,12 @@ impl<'de> Deserialize<'de> for Attribute {
                             }
                             generator = Some(Generator::Reuse(map.next_value()?));
                         }
+                        Field::Copy => {
+                            if generator.is_some() {
+                                return Err(de::Error::duplicate_field("generator"));
+                            }
+                            generator = Some(Generator::Same(map.next_value()?));
+                        }
                         Field::Nothing => {
                             if generator.is_some() {
                                 return Err(de::Error::duplicate_field("generator"));
@@ -101,18 +210,113 @@ impl<'de> Deserialize<'de> for Attribute {
                 let replace = replace.unwrap_or(false);
                 let chance = chance.unwrap_or(None);
                 let requires = requires.unwrap_or_else(Vec::new);
+                let remove = remove.unwrap_or_else(Vec::new);
                 Ok(Attribute {
                     generator,
                     replace,
                     chance,
                     requires,
+                    remove,
+                    origin,
+                    alias,
+                    label,
+                    description,
                 })
             }
         }
 
-        const FIELDS: &'static [&'static str] =
-            &["choose", "nothing", "replace", "chance", "requires"];
-        deserializer.deserialize_struct("Attribute", FIELDS, AttributeVisitor)
+        deserializer.deserialize_any(AttributeVisitor)
+    }
+}
+
+///Options to choose from, either a map of options or a list of option names
+struct Choices(Attributes);
+
+impl<'de> Deserialize<'de> for Choices {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        use std::fmt;
+        use serde::de::{Visitor, MapAccess, SeqAccess};
+
+        struct ChoicesVisitor;
+        impl<'de> Visitor<'de> for ChoicesVisitor {
+            type Value = Choices;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("map of options or list of option names")
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+            where
+                A: SeqAccess<'de>,
+            {
+                let mut choices = Attributes::new();
+                while let Some(name) = seq.next_element::<String>()? {
+                    if choices.contains_key(&name) {
+                        return Err(de::Error::custom(format!("duplicate option {}", name)));
+                    }
+                    choices.insert(name, Default::default());
+                }
+                Ok(Choices(choices))
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+            where
+                A: MapAccess<'de>,
+            {
+                let mut choices = Attributes::new();
+                while let Some(name) = map.next_key::<String>()? {
+                    if choices.contains_key(&name) {
+                        return Err(de::Error::custom(format!("duplicate option {}", name)));
+                    }
+                    choices.insert(name, map.next_value()?);
+                }
+                Ok(Choices(choices))
+            }
+        }
+
+        deserializer.deserialize_any(ChoicesVisitor)
+    }
+}
+
+///Options written as a list of names when none of them have anything set
+struct CompactChoices<'a>(&'a Attributes);
+
+impl<'a> Serialize for CompactChoices<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        use serde::ser::SerializeMap;
+        let choices = self.0;
+        if choices.values().all(Attribute::is_empty) {
+            return serializer.collect_seq(choices.keys());
+        }
+        //chances go first since toml can't have plain values after tables
+        let (chances, others): (Vec<_>, Vec<_>) = choices.iter().partition(|&(_, value)| value.chance.is_some() && value.chance_only());
+        let mut map = serializer.serialize_map(Some(choices.len()))?;
+        for (name, value) in chances.into_iter().chain(others) {
+            map.serialize_entry(name, value)?;
+        }
+        map.end()
+    }
+}
+
+impl Attribute {
+    fn is_empty(&self) -> bool {
+        self.chance.is_none() && self.chance_only()
+    }
+
+    ///Nothing other than possibly a chance, so it can be written as just the chance
+    fn chance_only(&self) -> bool {
+        match self.generator {
+            Generator::Nothing => {}
+            _ => return false,
+        }
+        !self.replace && self.requires.is_empty() && self.remove.is_empty() && self.origin.is_none() &&
+            self.alias.is_none() && self.label.is_none() && self.description.is_none()
     }
 }
 
@@ -122,28 +326,129 @@ impl Serialize for Attribute {
         S: Serializer,
     {
         use serde::ser::SerializeMap;
-        let mut map = serializer.serialize_map(Some(4))?;
-        match &self.generator {
-            &Generator::Choose(ref choices) => {
-                map.serialize_entry("choose", choices)?;
-            }
-            &Generator::Reuse(ref attribute_name) => {
-                map.serialize_entry("reuse", attribute_name)?;
-            }
-            &Generator::Same(ref attribute_name) => {
-                map.serialize_entry("copy", attribute_name)?;
-            }
-            &Generator::Nothing => {}
+        if let (Some(chance), true) = (self.chance, self.chance_only()) {
+            return serializer.collect_str(&format_args!("{:?}", chance));
         }
+        //a map rather than a struct so it reads back the same way as the chance shorthand in ron
+        let mut state = serializer.serialize_map(None)?;
+        //plain values go before the generator since toml can't have them after a table
         if self.replace {
-            map.serialize_entry("replace", &self.replace)?;
+            state.serialize_entry("replace", &self.replace)?;
         }
         if self.chance.is_some() {
-            map.serialize_entry("chance", &self.chance)?;
+            state.serialize_entry("chance", &self.chance)?;
         }
         if !self.requires.is_empty() {
-            map.serialize_entry("requires", &self.requires)?;
+            state.serialize_entry("requires", &self.requires)?;
+        }
+        if !self.remove.is_empty() {
+            state.serialize_entry("remove", &self.remove)?;
+        }
+        if let Some(ref label) = self.label {
+            state.serialize_entry("label", label)?;
+        }
+        if let Some(ref description) = self.description {
+            state.serialize_entry("description", description)?;
+        }
+        if let Some(ref alias) = self.alias {
+            state.serialize_entry("alias", alias)?;
+        }
+        if let Some(ref origin) = self.origin {
+            state.serialize_entry("from", origin)?;
+        }
+        match self.generator {
+            Generator::Choose(ref choices) => {
+                state.serialize_entry("choose", &CompactChoices(choices))?;
+            }
+            Generator::Reuse(ref attribute_name) => {
+                state.serialize_entry("reuse", attribute_name)?;
+            }
+            Generator::Same(ref attribute_name) => {
+                state.serialize_entry("copy", attribute_name)?;
+            }
+            Generator::Nothing => {}
+        }
+        state.end()
+    }
+}
+
+///Attributes in the order they get generated, then any others
+struct OrderedAttributes<'a>(&'a Template);
+
+impl<'a> Serialize for OrderedAttributes<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        use serde::ser::SerializeMap;
+        let template = self.0;
+        let mut names: Vec<&String> = Vec::new();
+        for name in &template.order {
+            let name = template.rename.get(name).unwrap_or(name);
+            if template.attributes.contains_key(name) && !names.contains(&name) {
+                names.push(name);
+            }
+        }
+        for name in template.attributes.keys() {
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+        let mut map = serializer.serialize_map(Some(names.len()))?;
+        for name in names {
+            map.serialize_entry(name, &template.attributes[name])?;
         }
         map.end()
     }
 }
+
+impl Serialize for Template {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        use serde::ser::SerializeStruct;
+        let mut state = serializer.serialize_struct("Template", 12)?;
+        if !self.extends.is_empty() {
+            state.serialize_field("extends", &self.extends)?;
+        }
+        if !self.mixins.is_empty() {
+            state.serialize_field("mixins", &self.mixins)?;
+        }
+        state.serialize_field("order", &self.order)?;
+        if self.auto_order {
+            state.serialize_field("auto_order", &self.auto_order)?;
+        }
+        if !self.remove.is_empty() {
+            state.serialize_field("remove", &self.remove)?;
+        }
+        if !self.disable.is_empty() {
+            state.serialize_field("disable", &self.disable)?;
+        }
+        state.serialize_field("rename", &self.rename)?;
+        state.serialize_field("formatting", &self.formatting)?;
+        state.serialize_field("attributes", &OrderedAttributes(self))?;
+        if !self.locales.is_empty() {
+            state.serialize_field("locales", &self.locales)?;
+        }
+        if !self.sections.is_empty() {
+            state.serialize_field("sections", &self.sections)?;
+        }
+        state.end()
+    }
+}
+
+#[test]
+fn test_shorthand() {
+    use super::Format;
+    let short = r#"{"order": ["size"], "attributes": {"size": {"choose": {"small": {}, "huge": "Rare", "large": {"requires": ["species:obj"]}}}}}"#;
+    let template: Template = short.parse().unwrap();
+    let json = Format::Json.serialize(&template).unwrap();
+    assert!(json.contains(r#""huge": "Rare""#));
+    assert!(json.find(r#""huge""#).unwrap() < json.find(r#""large""#).unwrap());
+    let list: Template = r#"{"attributes": {"size": {"choose": ["small", "large"]}}}"#.parse().unwrap();
+    assert_eq!(Format::Json.serialize(&list.attributes["size"]).unwrap(), "{\n  \"choose\": [\n    \"large\",\n    \"small\"\n  ]\n}");
+    assert!(r#"{"attributes": {"size": {"choose": ["small", "small"]}}}"#.parse::<Template>().is_err());
+    let error = r#"{"attributes": {"size": {"choose": {"small": {}, "small": "Rare"}}}}"#.parse::<Template>().unwrap_err();
+    assert!(error.starts_with("duplicate option small"), "{}", error);
+}
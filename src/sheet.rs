@@ -0,0 +1,129 @@
+use super::{Generated, Locale, Template};
+
+///Character sheets with one group of attributes per section, in the template's order
+///
+///Attributes that aren't in any section come first without a heading
+fn groups<'a>(template: &'a Template, generated: &Generated) -> Vec<(Option<&'a str>, Vec<&'a str>)> {
+    let mut ungrouped = Vec::new();
+    let mut grouped: Vec<(Option<&str>, Vec<&str>)> =
+        template.sections.iter().map(|section| (Some(section.name.as_str()), Vec::new())).collect();
+    for name in &template.order {
+        let name = template.rename.get(name).unwrap_or(name).as_str();
+        if !generated.contains_key(name) || ungrouped.contains(&name) {
+            continue;
+        }
+        match template.sections.iter().position(|section| section.attributes.iter().any(|attribute| attribute == name)) {
+            Some(i) => {
+                if !grouped[i].1.contains(&name) {
+                    grouped[i].1.push(name)
+                }
+            }
+            None => ungrouped.push(name),
+        }
+    }
+    grouped.insert(0, (None, ungrouped));
+    grouped.into_iter().filter(|(_, attributes)| !attributes.is_empty()).collect()
+}
+
+///Description of the value an attribute was given
+fn description<'a>(locale: &'a Locale, generated: &Generated, name: &str) -> Option<&'a str> {
+    locale.description(name, Some(&generated[name]))
+}
+
+///Markdown tables, with a description column for sections where any value has one
+pub fn markdown(template: &Template, generated: &Generated, locale: &Locale) -> String {
+    //a line break would end the row
+    let escape = |s: &str| s.replace('|', "\\|").replace("\r\n", "<br>").replace(['\n', '\r'], "<br>");
+    let mut output = Vec::new();
+    for (section, attributes) in groups(template, generated) {
+        if let Some(section) = section {
+            output.push(format!("## {}\n", section));
+        }
+        let described = attributes.iter().any(|name| description(locale, generated, name).is_some());
+        if described {
+            output.push("| Attribute | Value | Description |".to_string());
+            output.push("| --- | --- | --- |".to_string());
+        } else {
+            output.push("| Attribute | Value |".to_string());
+            output.push("| --- | --- |".to_string());
+        }
+        for name in attributes {
+            let mut row = format!("| {} | {} |", escape(locale.attribute(name)), escape(locale.value(name, &generated[name])));
+            if described {
+                row += &format!(" {} |", escape(description(locale, generated, name).unwrap_or("")));
+            }
+            output.push(row);
+        }
+        output.push(String::new());
+    }
+    output.join("\n")
+}
+
+///Definition lists, descriptions are shown as tooltips
+pub fn html(template: &Template, generated: &Generated, locale: &Locale) -> String {
+    let title = |description: Option<&str>| description.map_or(String::new(), |description| format!(" title=\"{}\"", escape_html(description)));
+    let mut output = Vec::new();
+    for (section, attributes) in groups(template, generated) {
+        output.push("<section>".to_string());
+        if let Some(section) = section {
+            output.push(format!("<h2>{}</h2>", escape_html(section)));
+        }
+        output.push("<dl>".to_string());
+        for name in attributes {
+            output.push(format!(
+                "<dt{}>{}</dt><dd{}>{}</dd>",
+                title(locale.description(name, None)),
+                escape_html(locale.attribute(name)),
+                title(description(locale, generated, name)),
+                escape_html(locale.value(name, &generated[name]))
+            ));
+        }
+        output.push("</dl>".to_string());
+        output.push("</section>".to_string());
+    }
+    output.join("\n")
+}
+
+pub fn escape_html(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[test]
+fn test_sheet() {
+    let template = Template::new_from_string(r#"{"order": ["species", "eyes", "mouth"], "attributes": {
+        "species": {"choose": {"obj": {}}},
+        "eyes": {"choose": {"<round>": {}}},
+        "mouth": {"choose": {"jagged|line": {}}, "label": "Mouth"}
+    }, "sections": [{"name": "Head", "attributes": ["mouth", "eyes"]}]}"#, None);
+    let generated = template.generate(Vec::new());
+    assert_eq!(
+        template.format(&generated, "markdown").unwrap(),
+        "| Attribute | Value |\n| --- | --- |\n| species | obj |\n\n## Head\n\n| Attribute | Value |\n| --- | --- |\n| eyes | <round> |\n| Mouth | jagged\\|line |\n"
+    );
+    assert!(template.format(&generated, "html").unwrap().contains("<h2>Head</h2>\n<dl>\n<dt>eyes</dt><dd>&lt;round&gt;</dd>"));
+
+    let template = Template::new_from_string(r#"{"order": ["species", "eyes"], "attributes": {
+        "species": {"choose": {"obj": {}}, "description": "what it is"},
+        "eyes": {"choose": {"round": {"description": "wide\n\"open\""}}}
+    }}"#, None);
+    let generated = template.generate(Vec::new());
+    assert_eq!(
+        template.format(&generated, "markdown").unwrap(),
+        "| Attribute | Value | Description |\n| --- | --- | --- |\n| species | obj |  |\n| eyes | round | wide<br>\"open\" |\n"
+    );
+    assert_eq!(
+        template.format(&generated, "html").unwrap(),
+        "<section>\n<dl>\n<dt title=\"what it is\">species</dt><dd>obj</dd>\n<dt>eyes</dt><dd title=\"wide\n&quot;open&quot;\">round</dd>\n</dl>\n</section>"
+    );
+}
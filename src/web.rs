@@ -0,0 +1,201 @@
+//!JavaScript interface, built with `wasm-pack build --target web` which writes the module and its
+//!TypeScript definitions to `pkg/`. `index.html` loads it from there and has to be served over http,
+//!browsers don't load modules from files. `wasm-pack test --node` runs the tests below. Templates are
+//!shared with the C interface
+
+use super::{Format, Generated, Locale, Mutations, Requirement, Trace, seeded, templates};
+use rand;
+use serde::Serialize;
+use serde_wasm_bindgen::{self, Serializer};
+use wasm_bindgen::JsCast;
+use wasm_bindgen::prelude::*;
+
+#[wasm_bindgen(typescript_custom_section)]
+const TYPES: &'static str = r#"
+/** A generated character, `trace` has the options chosen on the way to each value */
+export interface Character {
+    values: Record<string, string>;
+    trace: Record<string, string[]>;
+    /** names of the mutations applied */
+    mutations: string[];
+    /** pass to `generate` again to get the same character */
+    seed: number;
+}
+
+/** Anything `requires` accepts, like `"color:red"` or `"!size:large"` */
+export type Preset = string;
+"#;
+
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(typescript_type = "Character")]
+    pub type JsCharacter;
+    #[wasm_bindgen(typescript_type = "Preset[]")]
+    pub type Presets;
+    #[wasm_bindgen(typescript_type = "string[]")]
+    pub type Names;
+}
+
+#[derive(Serialize, Deserialize)]
+struct Character {
+    values: Generated,
+    trace: Trace,
+    #[serde(default)]
+    mutations: Vec<String>,
+    #[serde(default)]
+    seed: u64,
+}
+
+///Plain objects rather than `Map`s, so values can be read with `character.values.color`
+fn to_js<T: Serialize>(value: &T) -> Result<JsValue, JsError> {
+    value.serialize(&Serializer::json_compatible()).map_err(|e| JsError::new(&e.to_string()))
+}
+
+fn format(format: Option<String>) -> Result<Format, JsError> {
+    match format {
+        None => Ok(Format::Json),
+        Some(format) => Format::from_extension(&format).ok_or_else(|| JsError::new(&format!("unknown format {}", format))),
+    }
+}
+
+///Register a template from a string under a name, replacing any template already called that.
+///`format` is `json`, `yaml`, `toml` or `ron` and json if left out
+#[wasm_bindgen(js_name = registerTemplate)]
+pub fn register_template(name: &str, source: &str, format: Option<String>) -> Result<(), JsError> {
+    let template = self::format(format)?.parse(source).map_err(|e| JsError::new(&e))?;
+    templates().register(name, template).map_err(|e| JsError::new(&e))
+}
+
+///Unregister a template nothing else inherits from
+#[wasm_bindgen(js_name = unregisterTemplate)]
+pub fn unregister_template(name: &str) -> Result<(), JsError> {
+    templates().remove(name).map(|_| ()).map_err(|e| JsError::new(&e))
+}
+
+///Names of the registered templates
+#[wasm_bindgen(js_name = templateNames)]
+pub fn template_names() -> Result<Names, JsError> {
+    Ok(to_js(&templates().names())?.unchecked_into())
+}
+
+///A resolved template, the mutations it can be generated with and its labels for formatting
+#[wasm_bindgen(js_name = Template)]
+pub struct JsTemplate {
+    template: super::Template,
+    mutations: Mutations,
+    labels: Locale,
+}
+
+impl JsTemplate {
+    fn new(template: super::Template, mutations: &Mutations) -> JsTemplate {
+        let labels = template.labels();
+        JsTemplate { template, mutations: mutations.clone(), labels }
+    }
+}
+
+#[wasm_bindgen(js_class = Template)]
+impl JsTemplate {
+    ///Get a registered template by name, later changes to the registry don't affect it
+    pub fn get(name: &str) -> Result<JsTemplate, JsError> {
+        let templates = templates();
+        let template = templates.get(name).ok_or_else(|| JsError::new(&format!("unknown template {}", name)))?;
+        Ok(JsTemplate::new(template.clone(), templates.mutations()))
+    }
+
+    ///Load a template from a string without registering it, `format` is as for `registerTemplate`. It
+    ///can inherit from any registered template
+    pub fn load(source: &str, format: Option<String>) -> Result<JsTemplate, JsError> {
+        let template = self::format(format)?.parse(source).map_err(|e| JsError::new(&e))?;
+        let templates = templates();
+        let template = templates.resolve_with("loaded", &template).map_err(|e| JsError::new(&e))?;
+        Ok(JsTemplate::new(template, templates.mutations()))
+    }
+
+    ///Attributes in the order they are generated
+    pub fn attributes(&self) -> Result<Names, JsError> {
+        Ok(to_js(&self.template.columns())?.unchecked_into())
+    }
+
+    ///Generate a character, the same seed and presets give the same character with the same version of
+    ///morbitgen. `seed` has to be a whole number, a random one is used if left out
+    pub fn generate(&self, presets: Option<Presets>, seed: Option<f64>) -> Result<JsCharacter, JsError> {
+        let presets: Vec<Requirement> = match presets {
+            Some(presets) => serde_wasm_bindgen::from_value(presets.into()).map_err(|e| JsError::new(&e.to_string()))?,
+            None => Vec::new(),
+        };
+        let seed = match seed {
+            //anything bigger can't be passed back exactly
+            Some(seed) if (0.0..=9_007_199_254_740_991.0).contains(&seed) && seed.fract() == 0.0 => seed as u64,
+            Some(seed) => return Err(JsError::new(&format!("seed {} has to be a whole number", seed))),
+            None => rand::random::<u32>().into(),
+        };
+        let mut random = seeded(seed);
+        let (values, trace, mutations) = self
+            .template
+            .generate_mutated_traced_with_rng(presets, &self.mutations, &mut random)
+            .map_err(|e| JsError::new(&e))?;
+        Ok(to_js(&Character { values, trace, mutations, seed })?.unchecked_into())
+    }
+
+    ///Format a character with a formatting entry or string, or `json`, `markdown` and `html`
+    pub fn format(&self, character: JsCharacter, formatting: &str) -> Result<String, JsError> {
+        let character: Character =
+            serde_wasm_bindgen::from_value(character.into()).map_err(|e| JsError::new(&e.to_string()))?;
+        self.template.format_with(&character.values, formatting, &self.labels).map_err(|e| JsError::new(&e))
+    }
+
+    ///One line per attribute showing the options chosen to get to its value
+    pub fn explain(&self, character: JsCharacter) -> Result<String, JsError> {
+        let character: Character =
+            serde_wasm_bindgen::from_value(character.into()).map_err(|e| JsError::new(&e.to_string()))?;
+        Ok(self.template.explain(&character.values, &character.trace))
+    }
+}
+
+#[cfg(test)]
+use wasm_bindgen_test::wasm_bindgen_test;
+
+#[cfg(test)]
+fn character(character: JsCharacter) -> Character {
+    serde_wasm_bindgen::from_value(character.into()).unwrap()
+}
+
+#[cfg(test)]
+#[wasm_bindgen_test]
+fn test_generate() {
+    let template = JsTemplate::get("obj").unwrap();
+    let presets: Presets = to_js(&["flavor:normal"]).unwrap().unchecked_into();
+    let first = character(template.generate(Some(presets), Some(7.0)).unwrap());
+    assert_eq!(first.values["flavor"], "normal");
+    assert_eq!(first.seed, 7);
+    let second = character(template.generate(None, Some(7.0)).unwrap());
+    assert_eq!(second.seed, 7);
+    assert!(template.generate(None, Some(0.5)).is_err());
+    assert!(template.generate(None, Some(-1.0)).is_err());
+
+    //what `generate` returns can be passed back, with or without the fields `format` doesn't need
+    let values: JsCharacter = to_js(&first).unwrap().unchecked_into();
+    assert_eq!(template.format(values, "[species]").unwrap(), "obj");
+    let values: JsCharacter = to_js(&json!({"values": {"species": "obj"}, "trace": {}})).unwrap().unchecked_into();
+    assert_eq!(template.format(values, "[species]").unwrap(), "obj");
+}
+
+#[cfg(test)]
+#[wasm_bindgen_test]
+fn test_register() {
+    register_template("web parent", "order: [size]\nattributes:\n  size:\n    choose: [tiny]", Some("yaml".to_string()))
+        .unwrap();
+    register_template("web child", r#"{"extends": "web parent", "order": ["mood"], "attributes": {"mood": {"choose": ["calm"]}}}"#, None)
+        .unwrap();
+    assert!(register_template("web broken", "{}", Some("xml".to_string())).is_err());
+    let template = JsTemplate::get("web child").unwrap();
+    let names: Vec<String> = serde_wasm_bindgen::from_value(template.attributes().unwrap().into()).unwrap();
+    assert_eq!(names, vec!["size", "mood"]);
+    let generated = character(template.generate(None, Some(0.0)).unwrap());
+    assert_eq!(generated.values["mood"], "calm");
+    assert!(generated.mutations.is_empty());
+    assert!(unregister_template("web parent").is_err());
+    unregister_template("web child").unwrap();
+    unregister_template("web parent").unwrap();
+    assert!(JsTemplate::get("web child").is_err());
+}